@@ -0,0 +1,306 @@
+//! An interactive debugger, started with `coli debug file.col`.
+
+use std::collections::BTreeMap;
+use std::io::{stdin, stdout, BufRead, Write};
+
+use col::interpreter::{Breakpoint, BreakpointId, ExecutionStatus, Interpreter, Sendable, StopReason};
+use col::parser::Program;
+use col::program::{ProgramState, SimpleProgramState};
+use col::value::Value;
+
+const HELP: &str = "\
+commands:
+  step [n], s [n]          execute n steps, or one
+  continue, c              run until a breakpoint, or until the program stops
+  break <column>:<ip>, b   stop before the instruction at ip in a column
+  break <column>           stop whenever a column is entered with `;`
+  delete [breakpoint], d   remove a breakpoint, or all of them
+  breakpoints              list the breakpoints
+  where, w                 show the current instruction
+  stack [index]            show a stack, or the local and remote stacks
+  stacks                   show every stack
+  set <index> [values...]  replace the values of a stack, the top value last
+  input <text>             give the program a line of input
+  eof                      end the input of the program
+  restart                  start the program again, keeping the breakpoints
+  help, h                  show the commands
+  quit, q                  leave the debugger
+
+an empty line repeats the last command";
+
+/// Where a breakpoint was set: a column, and the instruction pointer unless it stops whenever
+/// the column is entered with `;`.
+type Place = (u32, Option<u32>);
+
+/// The program being debugged, and everything the debugger keeps between commands.
+struct Debugger<'a, V: Value> {
+	program: &'a Program,
+	interpreter: Interpreter<'static, SimpleProgramState<V>, Sendable>,
+	/// The breakpoints of the interpreter, kept to list them and to set them again on restart
+	breakpoints: BTreeMap<Place, BreakpointId>,
+}
+
+/// Debug a program, reading commands from stdin until told to quit or the commands end.
+pub fn debug<V: Value>(program: &Program) {
+	let mut debugger = Debugger::<V>::new(program);
+	let mut last = String::new();
+
+	println!("type `help` for the commands");
+	debugger.print_where();
+
+	let stdin = stdin();
+
+	loop {
+		print!("(col) ");
+		let _ = stdout().flush();
+
+		let mut line = String::new();
+
+		match stdin.lock().read_line(&mut line) {
+			Ok(0) | Err(_) => break,
+			Ok(_) => {},
+		}
+
+		let line = line.trim();
+
+		// repeat the last command, which is handy for stepping
+		if !line.is_empty() {
+			last = line.to_string();
+		}
+
+		if !debugger.execute(&last) {
+			break;
+		}
+	}
+}
+
+impl<'a, V: Value> Debugger<'a, V> {
+	fn new(program: &'a Program) -> Self {
+		Debugger {
+			program,
+			interpreter: start(program),
+			breakpoints: BTreeMap::new(),
+		}
+	}
+
+	/// Execute a command, returning whether to keep going.
+	fn execute(&mut self, line: &str) -> bool {
+		let (command, rest) = match line.find(' ') {
+			Some(index) => (&line[..index], line[index + 1..].trim()),
+			None => (line, ""),
+		};
+
+		match command {
+			"" => {},
+			"step" | "s" => match parse_count(rest) {
+				Some(count) => self.resume(Some(count)),
+				None => println!("error: {} is not a number of steps", rest),
+			},
+			"continue" | "c" => self.resume(None),
+			"break" | "b" => match parse_place(rest) {
+				Some(place) => {
+					let interpreter = &mut self.interpreter;
+					self.breakpoints.entry(place).or_insert_with(|| interpreter.add_breakpoint(breakpoint(place)));
+					println!("breakpoint {}", describe(place));
+				},
+				None => println!("error: a breakpoint is a column, or a column and an instruction pointer such as 0:3"),
+			},
+			"delete" | "d" if rest.is_empty() => {
+				for (_, id) in std::mem::take(&mut self.breakpoints) {
+					self.interpreter.remove_breakpoint(id);
+				}
+
+				println!("deleted every breakpoint");
+			},
+			"delete" | "d" => match parse_place(rest).and_then(|place| Some((place, self.breakpoints.remove(&place)?))) {
+				Some((place, id)) => {
+					self.interpreter.remove_breakpoint(id);
+					println!("deleted breakpoint {}", describe(place));
+				},
+				None => println!("error: there is no breakpoint {}", rest),
+			},
+			"breakpoints" => {
+				if self.breakpoints.is_empty() {
+					println!("no breakpoints");
+				}
+
+				for place in self.breakpoints.keys() {
+					match place {
+						(_, Some(_)) => println!("{}", describe(*place)),
+						(_, None) => println!("{} (entering the column)", describe(*place)),
+					}
+				}
+			},
+			"where" | "w" => self.print_where(),
+			"stack" if rest.is_empty() => {
+				let (local, remote) = (self.interpreter.local_column(), self.interpreter.remote_column());
+
+				self.print_stack(local, "local");
+				self.print_stack(remote, if local == remote { "remote, which is the local stack" } else { "remote" });
+			},
+			"stack" => match rest.parse() {
+				Ok(index) => self.print_stack(index, ""),
+				Err(_) => println!("error: {} is not a stack index", rest),
+			},
+			"stacks" => {
+				for (index, _) in self.interpreter.state().stacks() {
+					self.print_stack(index, "");
+				}
+			},
+			"set" => self.set(rest),
+			"input" => {
+				self.interpreter.push_input(format!("{}\n", rest).as_bytes());
+			},
+			"eof" => {
+				self.interpreter.close_input();
+			},
+			"restart" => {
+				self.interpreter = start(self.program);
+
+				for (place, id) in &mut self.breakpoints {
+					*id = self.interpreter.add_breakpoint(breakpoint(*place));
+				}
+
+				println!("restarted");
+				self.print_where();
+			},
+			"help" | "h" => println!("{}", HELP),
+			"quit" | "q" => return false,
+			_ => println!("error: unknown command {}, type `help` for the commands", command),
+		}
+
+		true
+	}
+
+	/// Run the program for some steps, or until it stops, stopping early at a breakpoint.
+	fn resume(&mut self, steps: Option<u64>) {
+		match self.interpreter.run_for(steps.unwrap_or(u64::MAX)) {
+			ExecutionStatus::Running => self.print_where(),
+			ExecutionStatus::Stopped { reason } => {
+				if let StopReason::Breakpoint { id, .. } = reason {
+					if let Some(place) = self.breakpoints.iter().find(|(_, other)| **other == id).map(|(place, _)| *place) {
+						println!("stopped at breakpoint {}", describe(place));
+					}
+				}
+
+				self.print_where();
+			},
+			ExecutionStatus::Terminated => {
+				println!("the program terminated after {} steps", self.interpreter.steps());
+			},
+			ExecutionStatus::NeedsInput => {
+				println!("the program is waiting for input, give it with `input <text>` or end it with `eof`");
+				self.print_where();
+			},
+			ExecutionStatus::Error(error) => {
+				println!("error: {}", error);
+				self.print_where();
+			},
+		}
+	}
+
+	/// Show the source of the local column, with a caret under the next instruction.
+	fn print_where(&self) {
+		let (column, ip) = (self.interpreter.local_column(), self.interpreter.ip());
+		let source = &self.program.columns()[column as usize];
+
+		print!("column {}, instruction {}, step {}", column, ip, self.interpreter.steps());
+
+		if self.interpreter.is_string_mode() {
+			print!(", pushing a string");
+		}
+
+		println!();
+
+		let number = format!("{:>4}", column);
+		println!("{} | {}", number, source.source());
+
+		if let Some(offset) = source.offset(ip) {
+			println!("{} | {}^", " ".repeat(number.len()), source.indent(offset));
+		}
+	}
+
+	fn print_stack(&self, index: u32, label: &str) {
+		let stack = match self.interpreter.state().nth(index) {
+			Some(stack) => stack.borrow(),
+			None => {
+				println!("{}: doesn't exist yet", index);
+				return;
+			},
+		};
+
+		let values: Vec<String> = stack.values().iter().map(V::to_string).collect();
+
+		if label.is_empty() {
+			println!("{}: [{}]", index, values.join(" "));
+		} else {
+			println!("{} ({}): [{}]", index, label, values.join(" "));
+		}
+	}
+
+	/// Replace the values of a stack, from arguments such as `3 1 2 3`.
+	fn set(&mut self, arguments: &str) {
+		let mut words = arguments.split_whitespace();
+
+		let index = match words.next().map(str::parse) {
+			Some(Ok(index)) => index,
+			_ => {
+				println!("error: the first value has to be a stack index");
+				return;
+			},
+		};
+
+		let mut values = Vec::new();
+
+		for word in words {
+			match word.parse() {
+				Ok(value) => values.push(value),
+				Err(_) => {
+					println!("error: {} isn't a value", word);
+					return;
+				},
+			}
+		}
+
+		self.interpreter.set_stack(index, values);
+		self.print_stack(index, "");
+	}
+}
+
+/// An interpreter at the start of the program, which takes its input from the debugger.
+fn start<V: Value>(program: &Program) -> Interpreter<'static, SimpleProgramState<V>, Sendable> {
+	Interpreter::from_owned(program.clone(), None, Some(Box::new(stdout())))
+		.with_suspending_input()
+		.with_output_buffer(0)
+}
+
+/// The breakpoint of the interpreter for where one was set.
+fn breakpoint<V: Value>((column, ip): Place) -> Breakpoint<'static, SimpleProgramState<V>, Sendable> {
+	match ip {
+		Some(ip) => Breakpoint::at(column, ip),
+		None => Breakpoint::entering(column),
+	}
+}
+
+/// Where a breakpoint was set, the way it's given to `break`.
+fn describe((column, ip): Place) -> String {
+	match ip {
+		Some(ip) => format!("{}:{}", column, ip),
+		None => column.to_string(),
+	}
+}
+
+fn parse_count(value: &str) -> Option<u64> {
+	if value.is_empty() {
+		return Some(1);
+	}
+
+	value.parse().ok().filter(|count| *count > 0)
+}
+
+fn parse_place(value: &str) -> Option<Place> {
+	match value.find(':') {
+		Some(index) => Some((value[..index].parse().ok()?, Some(value[index + 1..].parse().ok()?))),
+		None => Some((value.parse().ok()?, None)),
+	}
+}
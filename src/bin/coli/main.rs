@@ -0,0 +1,848 @@
+//#![feature(exclusive_range_pattern)]
+
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{self, stderr, stdout, stdin, BufWriter, Write};
+use std::process;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand, crate_version, crate_authors, value_t};
+
+use col::Error;
+use col::interpreter::{Backend, Charset, Coverage, InputMode, Interpreter, Limits, Observer, OverflowPolicy, Profiler, Recording, Snapshot, StrictMode, Unprintable};
+use col::parser::{self, Diagnostic, Instruction, Program, Span};
+use col::program::SimpleProgramState;
+use col::value::{BigInt, Value};
+
+mod debug;
+
+/// How many bytes of output are kept before they are written, unless unbuffered.
+const OUTPUT_BUFFER_SIZE: usize = 8192;
+
+/// How many of the most executed instructions are shown in a profile.
+const HOT_SPOTS: usize = 20;
+
+/// Settings for running a program, collected from the command line.
+struct Options {
+	delay: u64,
+	backend: Backend,
+	overflow_policy: OverflowPolicy,
+	strict: StrictMode,
+	charset: Charset,
+	unprintable: Option<Unprintable>,
+	input_mode: InputMode,
+	end_of_input: Option<String>,
+	unbuffered: bool,
+	limits: Limits,
+	timeout: Option<Duration>,
+	seed: Option<u64>,
+	deterministic: bool,
+	record: Option<String>,
+	replay: Option<Recording>,
+	save_state: Option<String>,
+	load_state: Option<String>,
+	trace: Option<TraceOptions>,
+	profile: Option<ProfileOptions>,
+	coverage: Option<CoverageOptions>,
+}
+
+/// Where to keep the coverage of a program.
+struct CoverageOptions {
+	/// The path of the program, as it's named in the lcov file
+	source: String,
+	/// The lcov file, which is added to if it exists
+	file: String,
+	/// Where to write the report, if anywhere, which is stderr if `None`
+	report: Option<Option<String>>,
+}
+
+/// Where to write a profile.
+struct ProfileOptions {
+	/// The file to write the report to, instead of stderr
+	file: Option<String>,
+	/// The file to write folded stacks to, if any
+	folded: Option<String>,
+}
+
+/// What to trace, and where to.
+struct TraceOptions {
+	/// The file to write to, instead of stderr
+	file: Option<String>,
+	/// Only steps in these columns are traced, if set
+	columns: Option<Vec<u32>>,
+	/// Trace every nth step
+	every: u64,
+	values: ValueFormat,
+}
+
+/// How the values of the stacks are shown in a trace.
+#[derive(Copy, Clone)]
+enum ValueFormat {
+	Decimal,
+	Hex,
+	/// Printable characters are shown quoted, and everything else in decimal
+	Char,
+}
+
+/// Writes a line for every traced step, with the instruction and the stacks it left.
+struct Tracer<'a> {
+	program: &'a Program,
+	output: Box<dyn Write>,
+	columns: Option<Vec<u32>>,
+	every: u64,
+	values: ValueFormat,
+	/// How many steps have been executed
+	steps: u64,
+	local_column: u32,
+	remote_column: u32,
+	/// The step being traced, if it is
+	pending: Option<String>,
+	/// The first error writing the trace, after which nothing more is written
+	error: Option<io::Error>,
+}
+
+fn main() {
+	let matches = App::new("coli")
+		.version(crate_version!())
+		.author(crate_authors!())
+		.about("col interpreter")
+		.setting(AppSettings::SubcommandsNegateReqs)
+		.subcommand(SubCommand::with_name("debug")
+			.about("Step through a program interactively, with breakpoints")
+			.arg(Arg::with_name("file")
+				.help("Source file to debug.")
+				.required(true)
+				.validator(validate_path))
+			.args(&value_args()))
+		.arg(Arg::with_name("file")
+			.help("Source file to interpret.")
+			.required(true)
+			.validator(validate_path))
+		.arg(Arg::with_name("step_delay")
+			.help("Milliseconds to delay between steps")
+			.takes_value(true)
+			.long("delay")
+			.required(false)
+			.default_value("0"))
+		.arg(Arg::with_name("backend")
+			.help("How to execute the program")
+			.takes_value(true)
+			.long("backend")
+			.possible_values(&["direct", "bytecode"])
+			.default_value("direct"))
+		.args(&value_args())
+		.arg(Arg::with_name("overflow")
+			.help("What happens when arithmetic overflows")
+			.takes_value(true)
+			.long("overflow")
+			.possible_values(&["wrap", "saturate", "trap"])
+			.default_value("wrap"))
+		.arg(Arg::with_name("strict")
+			.help("Stop the program when an operation fails, instead of using zero. \
+				Every failure is strict unless some are listed.")
+			.long("strict")
+			.takes_value(true)
+			.min_values(0)
+			.require_equals(true)
+			.use_delimiter(true)
+			.value_name("failures")
+			.possible_values(&["empty-local", "empty-remote", "empty-input", "division", "no-remote"]))
+		.arg(Arg::with_name("charset")
+			.help("How characters are read and printed")
+			.takes_value(true)
+			.long("charset")
+			.possible_values(&["utf8", "latin1", "bytes"])
+			.default_value("utf8"))
+		.arg(Arg::with_name("unprintable")
+			.help("What happens when a value which isn't a character is printed, \
+				by default an error for `$` and nothing for `p`")
+			.takes_value(true)
+			.long("unprintable")
+			.possible_values(&["error", "replace", "skip"]))
+		.arg(Arg::with_name("input_mode")
+			.help("How `_` reads the input: a character as soon as it's available, \
+				a character once its whole line is available, or a decimal number")
+			.takes_value(true)
+			.long("input-mode")
+			.possible_values(&["char", "line", "number"])
+			.default_value("char"))
+		.arg(Arg::with_name("end_of_input")
+			.help("Value pushed by `_` at the end of the input, instead of zero")
+			.takes_value(true)
+			.value_name("value")
+			.long("eof"))
+		.arg(Arg::with_name("unbuffered")
+			.help("Write output as soon as it's printed, instead of when the program reads input or stops")
+			.long("unbuffered"))
+		.arg(Arg::with_name("max_steps")
+			.help("Stop the program after this many steps")
+			.takes_value(true)
+			.long("max-steps"))
+		.arg(Arg::with_name("max_stack_depth")
+			.help("Stop the program when a stack holds more than this many values")
+			.takes_value(true)
+			.long("max-stack-depth"))
+		.arg(Arg::with_name("max_columns")
+			.help("Stop the program when more than this many stacks are in use")
+			.takes_value(true)
+			.long("max-columns"))
+		.arg(Arg::with_name("max_cells")
+			.help("Stop the program when all stacks together hold more than this many values")
+			.takes_value(true)
+			.long("max-cells"))
+		.arg(Arg::with_name("timeout")
+			.help("Stop the program after this many milliseconds")
+			.takes_value(true)
+			.long("timeout"))
+		.arg(Arg::with_name("seed")
+			.help("Seed for the random values pushed by `?`")
+			.takes_value(true)
+			.long("seed"))
+		.arg(Arg::with_name("deterministic")
+			.help("Make every run behave the same, given the same input and seed (zero by default)")
+			.long("deterministic"))
+		.arg(Arg::with_name("record")
+			.help("Record the input and random values of the run to a replay file")
+			.takes_value(true)
+			.value_name("file")
+			.long("record"))
+		.arg(Arg::with_name("replay")
+			.help("Repeat a recorded run, taking the input and random values from a replay file")
+			.takes_value(true)
+			.value_name("file")
+			.long("replay")
+			.conflicts_with("seed")
+			.validator(validate_path))
+		.arg(Arg::with_name("save_state")
+			.help("Save the stacks and everything else the interpreter holds to a state file when the program stops")
+			.takes_value(true)
+			.value_name("file")
+			.long("save-state"))
+		.arg(Arg::with_name("load_state")
+			.help("Continue from a state file. If the program had terminated, it starts again with the stacks kept")
+			.takes_value(true)
+			.value_name("file")
+			.long("load-state")
+			.validator(validate_path))
+		.arg(Arg::with_name("trace")
+			.help("Trace every step to stderr, or to a file, with the instruction and the stacks it left")
+			.long("trace")
+			.takes_value(true)
+			.min_values(0)
+			.require_equals(true)
+			.value_name("file"))
+		.arg(Arg::with_name("trace_columns")
+			.help("Only trace steps in these columns")
+			.takes_value(true)
+			.use_delimiter(true)
+			.value_name("columns")
+			.long("trace-columns")
+			.requires("trace"))
+		.arg(Arg::with_name("trace_every")
+			.help("Only trace every nth step")
+			.takes_value(true)
+			.value_name("n")
+			.long("trace-every")
+			.requires("trace"))
+		.arg(Arg::with_name("trace_values")
+			.help("How the values of the stacks are traced (decimal by default)")
+			.takes_value(true)
+			.long("trace-values")
+			.possible_values(&["decimal", "hex", "char"])
+			// no default value, which would always require --trace
+			.requires("trace"))
+		.arg(Arg::with_name("profile")
+			.help("Count how many times each instruction is executed, and report the hot spots and \
+				annotated source to stderr, or to a file, when the program stops")
+			.long("profile")
+			.takes_value(true)
+			.min_values(0)
+			.require_equals(true)
+			.value_name("file"))
+		.arg(Arg::with_name("profile_folded")
+			.help("Write the steps executed in each chain of columns entered with `;` as folded stacks, \
+				for flame graph tools")
+			.takes_value(true)
+			.value_name("file")
+			.long("profile-folded")
+			.requires("profile"))
+		.arg(Arg::with_name("coverage")
+			.help("Record which instructions are executed and which way every `[` goes to an lcov file, \
+				adding to what it already holds from earlier runs")
+			.takes_value(true)
+			.value_name("file")
+			.long("coverage"))
+		.arg(Arg::with_name("coverage_report")
+			.help("Report the coverage of every run so far, with the source annotated with what was never \
+				executed, to stderr or to a file")
+			.long("coverage-report")
+			.takes_value(true)
+			.min_values(0)
+			.require_equals(true)
+			.value_name("file")
+			.requires("coverage"))
+		.get_matches();
+
+	let debug_matches = matches.subcommand_matches("debug");
+	let file = debug_matches.unwrap_or(&matches).value_of("file").unwrap();
+	let source = std::fs::read_to_string(file).unwrap_or_else(|e| {
+		eprintln!("error: could not read {}: {}", file, e);
+		process::exit(1);
+	});
+
+	let program = parser::parse(&source);
+
+	for diagnostic in parser::check(&program) {
+		print_diagnostic(file, &program, &diagnostic);
+	}
+
+	if let Some(matches) = debug_matches {
+		if matches.is_present("bigint") {
+			debug::debug::<BigInt>(&program);
+		} else {
+			match matches.value_of("cell_type") {
+				Some("u8") => debug::debug::<u8>(&program),
+				Some("u16") => debug::debug::<u16>(&program),
+				Some("u64") => debug::debug::<u64>(&program),
+				Some("i64") => debug::debug::<i64>(&program),
+				_ => debug::debug::<u32>(&program),
+			}
+		}
+
+		return;
+	}
+
+	let delay = value_t!(matches.value_of("step_delay"), u64)
+		.unwrap_or_else(|e| e.exit()); // clean exit if invalid
+
+	let backend = match matches.value_of("backend") {
+		Some("bytecode") => Backend::Bytecode,
+		_ => Backend::Direct,
+	};
+
+	let overflow_policy = match matches.value_of("overflow") {
+		Some("saturate") => OverflowPolicy::Saturate,
+		Some("trap") => OverflowPolicy::Trap,
+		_ => OverflowPolicy::Wrap,
+	};
+
+	let strict = match matches.values_of("strict") {
+		Some(values) if values.len() > 0 => {
+			let mut strict = StrictMode::default();
+
+			for value in values {
+				match value {
+					"empty-local" => strict.empty_local_stack = true,
+					"empty-remote" => strict.empty_remote_stack = true,
+					"empty-input" => strict.empty_input = true,
+					"division" => strict.division_by_zero = true,
+					_ => strict.uninitialised_remote = true,
+				}
+			}
+
+			strict
+		},
+		_ if matches.is_present("strict") => StrictMode::all(),
+		_ => StrictMode::default(),
+	};
+
+	let charset = match matches.value_of("charset") {
+		Some("latin1") => Charset::Latin1,
+		Some("bytes") => Charset::Bytes,
+		_ => Charset::Utf8,
+	};
+
+	let unprintable = match matches.value_of("unprintable") {
+		Some("error") => Some(Unprintable::Error),
+		Some("replace") => Some(Unprintable::Replace),
+		Some("skip") => Some(Unprintable::Skip),
+		_ => None,
+	};
+
+	let input_mode = match matches.value_of("input_mode") {
+		Some("line") => InputMode::Line,
+		Some("number") => InputMode::Number,
+		_ => InputMode::Char,
+	};
+
+	let end_of_input = matches.value_of("end_of_input").map(String::from);
+	let unbuffered = matches.is_present("unbuffered");
+
+	let limits = Limits {
+		max_steps: optional_value(&matches, "max_steps"),
+		max_stack_depth: optional_value(&matches, "max_stack_depth"),
+		max_columns: optional_value(&matches, "max_columns"),
+		max_cells: optional_value(&matches, "max_cells"),
+	};
+
+	let timeout = optional_value(&matches, "timeout").map(Duration::from_millis);
+
+	let seed = optional_value(&matches, "seed");
+	let deterministic = matches.is_present("deterministic");
+
+	let record = matches.value_of("record").map(String::from);
+	let replay = matches.value_of("replay").map(|path| {
+		std::fs::File::open(path)
+			.and_then(Recording::read_from)
+			.unwrap_or_else(|e| {
+				eprintln!("error: could not read {}: {}", path, e);
+				process::exit(1);
+			})
+	});
+
+	let save_state = matches.value_of("save_state").map(String::from);
+	let load_state = matches.value_of("load_state").map(String::from);
+
+	let trace = if matches.is_present("trace") {
+		let columns = matches.values_of("trace_columns").map(|columns| {
+			columns.map(|column| column.parse().unwrap_or_else(|_| {
+				eprintln!("error: {} is not a column index", column);
+				process::exit(1);
+			})).collect()
+		});
+
+		let values = match matches.value_of("trace_values") {
+			Some("hex") => ValueFormat::Hex,
+			Some("char") => ValueFormat::Char,
+			_ => ValueFormat::Decimal,
+		};
+
+		Some(TraceOptions {
+			file: matches.value_of("trace").map(String::from),
+			columns,
+			every: optional_value(&matches, "trace_every").unwrap_or(1).max(1),
+			values,
+		})
+	} else {
+		None
+	};
+
+	let profile = if matches.is_present("profile") {
+		Some(ProfileOptions {
+			file: matches.value_of("profile").map(String::from),
+			folded: matches.value_of("profile_folded").map(String::from),
+		})
+	} else {
+		None
+	};
+
+	let coverage = matches.value_of("coverage").map(|path| CoverageOptions {
+		source: file.to_string(),
+		file: path.to_string(),
+		report: if matches.is_present("coverage_report") {
+			Some(matches.value_of("coverage_report").map(String::from))
+		} else {
+			None
+		},
+	});
+
+	let options = Options {
+		delay, backend, overflow_policy, strict, charset, unprintable, input_mode, end_of_input,
+		unbuffered, limits, timeout, seed, deterministic, record, replay, save_state, load_state,
+		trace, profile, coverage,
+	};
+
+	let result = if matches.is_present("bigint") {
+		run::<BigInt>(&program, &options)
+	} else {
+		match matches.value_of("cell_type") {
+			Some("u8") => run::<u8>(&program, &options),
+			Some("u16") => run::<u16>(&program, &options),
+			Some("u64") => run::<u64>(&program, &options),
+			Some("i64") => run::<i64>(&program, &options),
+			_ => run::<u32>(&program, &options),
+		}
+	};
+
+	if let Err(error) = result {
+		print_error(file, &program, &error);
+		process::exit(1);
+	}
+}
+
+fn run<V: Value>(program: &Program, options: &Options) -> Result<(), Error> {
+	let mut stdout = stdout();
+	let mut stdin = stdin();
+
+	// the tracer has to outlive the interpreter, to tell whether the trace could be written
+	let mut tracer = options.trace.as_ref().map(|trace| Tracer::new(program, trace));
+	let mut profiler = options.profile.as_ref().map(|_| Profiler::default());
+	let mut coverage = options.coverage.as_ref().map(|options| read_coverage(program, options));
+
+	let mut interpreter = Interpreter::<SimpleProgramState<V>>::from_program(program, Some(&mut stdin), Some(&mut stdout))
+		.with_backend(options.backend)
+		.with_overflow_policy(options.overflow_policy)
+		.with_strict_mode(options.strict)
+		.with_charset(options.charset)
+		.with_input_mode(options.input_mode)
+		.with_output_buffer(if options.unbuffered { 0 } else { OUTPUT_BUFFER_SIZE })
+		.with_limits(options.limits)
+		.with_deterministic(options.deterministic)
+		.with_recording(options.record.is_some());
+
+	if let Some(timeout) = options.timeout {
+		interpreter = interpreter.with_deadline(Instant::now() + timeout);
+	}
+
+	if let Some(unprintable) = options.unprintable {
+		interpreter = interpreter.with_unprintable(unprintable);
+	}
+
+	// the value is only parsed now, as it depends on the cell type
+	if let Some(value) = &options.end_of_input {
+		let value = value.parse().unwrap_or_else(|_| {
+			eprintln!("error: the end of input value {} doesn't fit the cell type", value);
+			process::exit(1);
+		});
+
+		interpreter = interpreter.with_end_of_input(value);
+	}
+
+	if let Some(seed) = options.seed {
+		interpreter = interpreter.with_seed(seed);
+	}
+
+	if let Some(recording) = &options.replay {
+		interpreter = interpreter.with_replay(recording.clone());
+	}
+
+	if let Some(path) = &options.load_state {
+		let mut snapshot = std::fs::File::open(path)
+			.and_then(Snapshot::read_from)
+			.unwrap_or_else(|e| {
+				eprintln!("error: could not read {}: {}", path, e);
+				process::exit(1);
+			});
+
+		// a program which terminated runs again, so its stacks persist between runs
+		if snapshot.is_terminated() {
+			snapshot.restart();
+		}
+
+		interpreter.restore(snapshot)?;
+	}
+
+	if let Some(tracer) = &mut tracer {
+		tracer.start(interpreter.steps(), interpreter.local_column(), interpreter.remote_column());
+	}
+
+	// observing the program slows it down, so only when asked to
+	if tracer.is_some() || profiler.is_some() || coverage.is_some() {
+		interpreter = interpreter.with_observer((tracer.as_mut(), profiler.as_mut(), coverage.as_mut()));
+	}
+
+	let result = interpreter.run_with_delay(options.delay);
+
+	// the recording is kept even if the program failed, since that's when it's needed most
+	if let (Some(path), Some(recording)) = (&options.record, interpreter.recording()) {
+		let mut file = Vec::new();
+
+		recording.write_to(&mut file)
+			.and_then(|_| std::fs::write(path, file))
+			.unwrap_or_else(|e| {
+				eprintln!("error: could not write {}: {}", path, e);
+				process::exit(1);
+			});
+	}
+
+	// as is the state, so a stopped program can be continued
+	if let Some(path) = &options.save_state {
+		let mut file = Vec::new();
+
+		interpreter.snapshot().write_to(&mut file)
+			.and_then(|_| std::fs::write(path, file))
+			.unwrap_or_else(|e| {
+				eprintln!("error: could not write {}: {}", path, e);
+				process::exit(1);
+			});
+	}
+
+	drop(interpreter);
+
+	if let Some(tracer) = tracer {
+		tracer.finish().unwrap_or_else(|e| {
+			eprintln!("error: could not write the trace: {}", e);
+			process::exit(1);
+		});
+	}
+
+	if let (Some(profiler), Some(profile)) = (&profiler, &options.profile) {
+		write_profile(program, profiler, profile);
+	}
+
+	// the coverage of a failed run counts too
+	if let (Some(coverage), Some(options)) = (&coverage, &options.coverage) {
+		write_coverage(coverage, options);
+	}
+
+	result
+}
+
+impl<'a> Tracer<'a> {
+	/// Create a tracer, exiting if the trace file can't be created.
+	fn new(program: &'a Program, options: &TraceOptions) -> Tracer<'a> {
+		let output: Box<dyn Write> = match &options.file {
+			Some(path) => Box::new(BufWriter::new(File::create(path).unwrap_or_else(|e| {
+				eprintln!("error: could not create {}: {}", path, e);
+				process::exit(1);
+			}))),
+			None => Box::new(stderr()),
+		};
+
+		Tracer {
+			program,
+			output,
+			columns: options.columns.clone(),
+			every: options.every,
+			values: options.values,
+			steps: 0,
+			local_column: 0,
+			remote_column: 0,
+			pending: None,
+			error: None,
+		}
+	}
+
+	/// Start tracing from where the interpreter is, which isn't the beginning if a state was loaded.
+	fn start(&mut self, steps: u64, local_column: u32, remote_column: u32) {
+		self.steps = steps;
+		self.local_column = local_column;
+		self.remote_column = remote_column;
+	}
+
+	/// Write whatever is left of the trace, returning the first error writing it.
+	fn finish(mut self) -> io::Result<()> {
+		match self.error.take() {
+			Some(error) => Err(error),
+			None => self.output.flush(),
+		}
+	}
+
+	fn format<V: Value>(&self, stack: &[V]) -> String {
+		let values: Vec<String> = stack.iter().map(|value| match self.values {
+			ValueFormat::Decimal => value.to_string(),
+			ValueFormat::Hex => hex(&value.to_string()),
+			ValueFormat::Char => match value.to_char() {
+				Some(c) if !c.is_control() => format!("{:?}", c),
+				_ => value.to_string(),
+			},
+		}).collect();
+
+		format!("[{}]", values.join(" "))
+	}
+}
+
+impl<'a, V: Value> Observer<V> for Tracer<'a> {
+	fn before_instruction(&mut self, column: u32, ip: u32, instruction: Instruction) {
+		let step = self.steps;
+		self.steps += 1;
+
+		let is_traced = step.is_multiple_of(self.every)
+			&& self.columns.as_ref().is_none_or(|columns| columns.contains(&column));
+
+		if !is_traced || self.error.is_some() {
+			return;
+		}
+
+		let c = self.program.column(column)
+			.and_then(|source| source.source().chars().nth(source.offset(ip)? as usize))
+			.unwrap_or(instruction.to_char());
+
+		self.pending = Some(format!("{} {}:{} {:?} {:?}", step, column, ip, c, instruction));
+	}
+
+	fn after_instruction(&mut self, local: &[V], remote: Option<&[V]>) {
+		let line = match self.pending.take() {
+			Some(line) => line,
+			None => return,
+		};
+
+		// the remote stack is the local one unless it's separate
+		let remote = match remote {
+			Some(remote) => self.format(remote),
+			None => String::from("local"),
+		};
+
+		let result = writeln!(self.output, "{} local {} {} remote {} {}",
+			line, self.local_column, self.format(local), self.remote_column, remote);
+
+		if let Err(error) = result {
+			self.error = Some(error);
+		}
+	}
+
+	fn on_column_switch(&mut self, _from: u32, to: u32) {
+		self.local_column = to;
+	}
+
+	fn on_remote_switch(&mut self, _from: u32, to: u32) {
+		self.remote_column = to;
+	}
+}
+
+/// Write the report of a profile, and its folded stacks if asked to, exiting if they can't be.
+fn write_profile(program: &Program, profiler: &Profiler, options: &ProfileOptions) {
+	let exit = |path: &str, e: io::Error| -> ! {
+		eprintln!("error: could not write {}: {}", path, e);
+		process::exit(1);
+	};
+
+	match &options.file {
+		Some(path) => {
+			let mut file = Vec::new();
+
+			profiler.write_report(program, HOT_SPOTS, &mut file)
+				.and_then(|_| std::fs::write(path, file))
+				.unwrap_or_else(|e| exit(path, e));
+		},
+		None => {
+			eprintln!();
+			profiler.write_report(program, HOT_SPOTS, stderr().lock())
+				.unwrap_or_else(|e| exit("the profile", e));
+		},
+	}
+
+	if let Some(path) = &options.folded {
+		let mut file = Vec::new();
+
+		profiler.write_folded(&mut file)
+			.and_then(|_| std::fs::write(path, file))
+			.unwrap_or_else(|e| exit(path, e));
+	}
+}
+
+/// The coverage of the earlier runs of a program, if there were any, exiting if it can't be read.
+fn read_coverage(program: &Program, options: &CoverageOptions) -> Coverage {
+	let mut coverage = Coverage::new(program);
+
+	match File::open(&options.file) {
+		Ok(file) => coverage.merge_lcov(file).unwrap_or_else(|e| {
+			eprintln!("error: could not read {}: {}", options.file, e);
+			process::exit(1);
+		}),
+		// this is the first run
+		Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+		Err(e) => {
+			eprintln!("error: could not read {}: {}", options.file, e);
+			process::exit(1);
+		},
+	}
+
+	coverage
+}
+
+/// Write the coverage, along with its report if asked to, exiting if they can't be.
+fn write_coverage(coverage: &Coverage, options: &CoverageOptions) {
+	let exit = |path: &str, e: io::Error| -> ! {
+		eprintln!("error: could not write {}: {}", path, e);
+		process::exit(1);
+	};
+
+	let mut file = Vec::new();
+
+	coverage.write_lcov(&options.source, &mut file)
+		.and_then(|_| std::fs::write(&options.file, file))
+		.unwrap_or_else(|e| exit(&options.file, e));
+
+	match &options.report {
+		Some(Some(path)) => {
+			let mut file = Vec::new();
+
+			coverage.write_report(&mut file)
+				.and_then(|_| std::fs::write(path, file))
+				.unwrap_or_else(|e| exit(path, e));
+		},
+		Some(None) => {
+			eprintln!();
+			coverage.write_report(stderr().lock()).unwrap_or_else(|e| exit("the coverage report", e));
+		},
+		None => {},
+	}
+}
+
+/// Show a decimal value in hexadecimal, as long as it isn't too large to convert.
+fn hex(decimal: &str) -> String {
+	match decimal.parse::<i128>() {
+		Ok(value) if value < 0 => format!("-0x{:x}", value.unsigned_abs()),
+		Ok(value) => format!("0x{:x}", value),
+		Err(_) => decimal.to_string(),
+	}
+}
+
+/// The arguments choosing the type of the values held in the stacks.
+fn value_args<'a, 'b>() -> [Arg<'a, 'b>; 2] {
+	[
+		Arg::with_name("cell_type")
+			.help("Type of the values held in the stacks")
+			.takes_value(true)
+			.long("cell-type")
+			.possible_values(&["u8", "u16", "u32", "u64", "i64"])
+			.default_value("u32"),
+		Arg::with_name("bigint")
+			.help("Use integers of unbounded size for the values held in the stacks")
+			.long("bigint")
+			.conflicts_with("cell_type"),
+	]
+}
+
+/// Parse the value of an argument which may not be present, exiting if it's invalid.
+fn optional_value<T>(matches: &ArgMatches, name: &str) -> Option<T> where T: FromStr, T::Err: Display {
+	if matches.is_present(name) {
+		Some(value_t!(matches, name, T).unwrap_or_else(|e| e.exit()))
+	} else {
+		None
+	}
+}
+
+fn validate_path(val: String) -> Result<(), String> {
+	let path = std::path::Path::new(&val);
+
+	if !path.exists() {
+		Err(String::from("The specified file could not be found"))
+	} else if !path.is_file() {
+		Err(String::from("The specified path is not a file"))
+	} else {
+		Ok(())
+	}
+}
+
+/// Print a diagnostic to stderr, with a caret under the offending characters.
+fn print_diagnostic(file: &str, program: &Program, diagnostic: &Diagnostic) {
+	eprintln!("warning: {}", diagnostic.kind);
+
+	if let Some(span) = diagnostic.span {
+		print_snippet(file, program, span);
+	}
+
+	eprintln!();
+}
+
+/// Print an error which stopped the program to stderr, pointing at the failing instruction.
+fn print_error(file: &str, program: &Program, error: &Error) {
+	eprintln!("error: {}", error);
+
+	let span = error.location().and_then(|location| {
+		// an empty column has no character to point at
+		let start = program.column(location.column)?.offset(location.ip)?;
+		Some(Span { line: location.column, start, end: start + 1 })
+	});
+
+	if let Some(span) = span {
+		print_snippet(file, program, span);
+	}
+}
+
+/// Print the source line of a span, with carets underneath it.
+fn print_snippet(file: &str, program: &Program, span: Span) {
+	let column = program.column(span.line);
+	let line = column.map(|column| column.source()).unwrap_or("");
+	let indent = column.map(|column| column.indent(span.start)).unwrap_or_default();
+	let number = (span.line + 1).to_string();
+	let gutter = " ".repeat(number.len());
+	let carets = "^".repeat((span.end - span.start).max(1) as usize);
+
+	eprintln!("{}--> {}:{}:{}", gutter, file, number, span.start + 1);
+	eprintln!("{} |", gutter);
+	eprintln!("{} | {}", number, line);
+	eprintln!("{} | {}{}", gutter, indent, carets);
+}
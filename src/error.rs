@@ -0,0 +1,191 @@
+//! Errors which can stop a col program.
+
+use std::fmt;
+use std::io;
+
+use crate::parser::Instruction;
+
+/// Where in a program something happened.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+	/// The index of the column being executed
+	pub column: u32,
+	/// The instruction pointer, as an index into the instructions of the column
+	pub ip: u32,
+}
+
+/// A condition which the interpreter was configured to stop the program at.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Trap {
+	/// Arithmetic overflowed, see [`OverflowPolicy`](../interpreter/enum.OverflowPolicy.html).
+	Overflow {
+		/// The instruction which overflowed
+		instruction: Instruction,
+		/// The top value of the stack when the instruction was executed
+		a: String,
+		/// The value below the top value when the instruction was executed
+		b: String,
+	},
+	/// A value was popped from an empty local stack.
+	EmptyLocalStack,
+	/// A value was popped from an empty remote stack.
+	EmptyRemoteStack,
+	/// A character was read after the end of the input.
+	EndOfInput,
+	/// A value was divided by zero, or the remainder of dividing by zero was taken.
+	DivisionByZero,
+	/// A value was moved to or from the remote stack while it is the local stack, such as before a
+	/// remote stack has been set with `~`.
+	UninitialisedRemote,
+}
+
+/// A resource limit, see [`Limits`](../interpreter/struct.Limits.html).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Limit {
+	/// More steps were executed than allowed
+	Steps(u64),
+	/// A stack held more values than allowed
+	StackDepth(usize),
+	/// More stacks were in use at once than allowed
+	Columns(u32),
+	/// All of the stacks together held more values than allowed
+	Cells(usize),
+}
+
+/// An error which stopped a col program.
+#[derive(Debug)]
+pub enum Error {
+	/// Reading input or writing output failed.
+	Io(io::Error),
+	/// A value printed as a character is not a character in the [charset](../interpreter/enum.Charset.html).
+	InvalidCodePoint {
+		value: String,
+		location: Location,
+	},
+	/// The program has no columns, so there is nothing to execute.
+	EmptyProgram,
+	/// The program was stopped by a trap.
+	Trap {
+		trap: Trap,
+		location: Location,
+	},
+	/// The program used more of a resource than allowed.
+	LimitExceeded {
+		limit: Limit,
+		location: Location,
+	},
+	/// The program was stopped by a [`CancellationHandle`](../interpreter/struct.CancellationHandle.html).
+	Cancelled {
+		/// How many steps had been executed
+		steps: u64,
+		location: Location,
+	},
+	/// The program was still running at its deadline.
+	DeadlineExceeded {
+		/// How many steps had been executed
+		steps: u64,
+		location: Location,
+	},
+	/// A [snapshot](../interpreter/struct.Snapshot.html) doesn't fit the program it was restored
+	/// into, such as when it is in a column the program doesn't have.
+	InvalidSnapshot(&'static str),
+	/// A [recording](../interpreter/struct.Recording.html) doesn't fit the program or the options
+	/// it's replayed with.
+	InvalidReplay(&'static str),
+	/// The program read more input or random values than its
+	/// [recording](../interpreter/struct.Recording.html) has.
+	ReplayEnded {
+		location: Location,
+	},
+}
+
+impl Trap {
+	/// Turn the trap into an error at the given location.
+	pub(crate) fn at(self, location: Location) -> Error {
+		Error::Trap { trap: self, location }
+	}
+}
+
+impl Limit {
+	/// Turn the limit into an error at the given location.
+	pub(crate) fn at(self, location: Location) -> Error {
+		Error::LimitExceeded { limit: self, location }
+	}
+}
+
+impl Error {
+	/// Where in the program the error happened, if it happened at a specific instruction.
+	pub fn location(&self) -> Option<Location> {
+		match self {
+			Error::InvalidCodePoint { location, .. }
+			| Error::Trap { location, .. }
+			| Error::LimitExceeded { location, .. }
+			| Error::Cancelled { location, .. }
+			| Error::DeadlineExceeded { location, .. }
+			| Error::ReplayEnded { location } => Some(*location),
+			Error::Io(_) | Error::EmptyProgram | Error::InvalidSnapshot(_) | Error::InvalidReplay(_) => None,
+		}
+	}
+}
+
+impl From<io::Error> for Error {
+	fn from(error: io::Error) -> Self {
+		Error::Io(error)
+	}
+}
+
+impl fmt::Display for Location {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "column {}, instruction {}", self.column, self.ip)
+	}
+}
+
+impl fmt::Display for Trap {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Trap::Overflow { instruction, a, b } => write!(f, "arithmetic overflow ({} {} {})", b, instruction.to_char(), a),
+			Trap::EmptyLocalStack => f.write_str("popped an empty local stack"),
+			Trap::EmptyRemoteStack => f.write_str("popped an empty remote stack"),
+			Trap::EndOfInput => f.write_str("read past the end of the input"),
+			Trap::DivisionByZero => f.write_str("division by zero"),
+			Trap::UninitialisedRemote => f.write_str("no remote stack has been set"),
+		}
+	}
+}
+
+impl fmt::Display for Limit {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Limit::Steps(max) => write!(f, "exceeded the limit of {} steps", max),
+			Limit::StackDepth(max) => write!(f, "exceeded the limit of {} values in a stack", max),
+			Limit::Columns(max) => write!(f, "exceeded the limit of {} columns", max),
+			Limit::Cells(max) => write!(f, "exceeded the limit of {} values in total", max),
+		}
+	}
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Error::Io(error) => write!(f, "I/O error: {}", error),
+			Error::InvalidCodePoint { value, location } => write!(f, "{} is not a valid character at {}", value, location),
+			Error::EmptyProgram => f.write_str("the program has no columns"),
+			Error::Trap { trap, location } => write!(f, "{} at {}", trap, location),
+			Error::LimitExceeded { limit, location } => write!(f, "{} at {}", limit, location),
+			Error::Cancelled { steps, location } => write!(f, "cancelled after {} steps at {}", steps, location),
+			Error::DeadlineExceeded { steps, location } => write!(f, "timed out after {} steps at {}", steps, location),
+			Error::InvalidSnapshot(reason) => write!(f, "the snapshot doesn't fit the program: {}", reason),
+			Error::InvalidReplay(reason) => write!(f, "the recording doesn't fit the program: {}", reason),
+			Error::ReplayEnded { location } => write!(f, "the recording ran out at {}", location),
+		}
+	}
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			Error::Io(error) => Some(error),
+			_ => None,
+		}
+	}
+}
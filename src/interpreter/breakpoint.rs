@@ -0,0 +1,295 @@
+//! Stopping a program where something interesting happens.
+
+use std::fmt;
+
+use crate::error::Location;
+use crate::parser::Instruction;
+use crate::program::ProgramState;
+
+use super::threading::{Local, Sendable, Threading};
+
+/// A condition on the local stack, and every other stack, for a breakpoint to stop.
+type BoxedCondition<'a, P, T> = Box<<T as Threading>::Condition<'a, P>>;
+
+/// Identifies a breakpoint, to remove it or to tell which one stopped the program.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BreakpointId(u32);
+
+/// Identifies a watchpoint, to remove it or to tell which one stopped the program.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WatchpointId(u32);
+
+/// Why a program [stopped](enum.ExecutionStatus.html#variant.Stopped) before it had to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StopReason {
+	/// A breakpoint was reached at `location`, and the instruction there hasn't been executed yet.
+	Breakpoint { id: BreakpointId, location: Location },
+	/// A watched stack was changed by the instruction at `location`, which has been executed.
+	Watchpoint { id: WatchpointId, location: Location },
+}
+
+/// Where a program stops before executing an instruction.
+///
+/// Add one with [`Interpreter::add_breakpoint`](struct.Interpreter.html#method.add_breakpoint).
+///
+/// # Example
+///
+/// ```
+/// use col::interpreter::{Breakpoint, ExecutionStatus, Interpreter, StopReason};
+/// use col::parser::Instruction;
+/// use col::program::SimpleProgramState;
+///
+/// let mut interpreter = Interpreter::<SimpleProgramState>::new("3[1-:]@", None, None);
+///
+/// // stop at the `]` once the counter reaches 1
+/// let id = interpreter.add_breakpoint(Breakpoint::on(Instruction::RightBracket).when(|local, _| local.last() == Some(&1)));
+///
+/// match interpreter.run_for(100) {
+///     ExecutionStatus::Stopped { reason: StopReason::Breakpoint { id: stopped, location } } => {
+///         assert_eq!(stopped, id);
+///         assert_eq!(location.ip, 5);
+///     },
+///     status => panic!("The program didn't stop: {:?}", status),
+/// }
+///
+/// // resuming executes the `]` rather than stopping at it again
+/// assert!(matches!(interpreter.run_for(100), ExecutionStatus::Terminated));
+/// ```
+pub struct Breakpoint<'a, P: ProgramState, T: Threading = Local> {
+	place: Place,
+	condition: Option<BoxedCondition<'a, P, T>>,
+}
+
+/// The instructions a breakpoint stops at.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Place {
+	At(Location),
+	Instruction(Instruction),
+	Entering(u32),
+}
+
+impl<'a, P: ProgramState, T: Threading> Breakpoint<'a, P, T> {
+	/// Stop before the instruction at `ip` in a column.
+	///
+	/// Characters inside a string aren't instructions, so a breakpoint there is never reached.
+	pub fn at(column: u32, ip: u32) -> Self {
+		Breakpoint { place: Place::At(Location { column, ip }), condition: None }
+	}
+
+	/// Stop before every instruction of a kind, such as every `@` or `;`.
+	///
+	/// A `Value` instruction only matches the same value.
+	pub fn on(instruction: Instruction) -> Self {
+		Breakpoint { place: Place::Instruction(instruction), condition: None }
+	}
+
+	/// Stop before the first instruction of a column after a `;` entered it, even from the same
+	/// column.
+	pub fn entering(column: u32) -> Self {
+		Breakpoint { place: Place::Entering(column), condition: None }
+	}
+
+	/// Only stop when a condition holds, given the values of the local stack, the top value last,
+	/// and every stack of the program.
+	///
+	/// The condition has to be `Send` for a [`Sendable`](struct.Sendable.html) interpreter.
+	pub fn when<F: Fn(&[P::Value], &P) -> bool + IntoCondition<'a, P, T>>(mut self, condition: F) -> Self {
+		self.condition = Some(condition.into_condition());
+		self
+	}
+
+	fn matches(&self, location: Location, instruction: Instruction, is_entering: bool, local: &[P::Value], state: &P) -> bool {
+		let is_here = match self.place {
+			Place::At(at) => at == location,
+			Place::Instruction(kind) => kind == instruction,
+			Place::Entering(column) => is_entering && column == location.column,
+		};
+
+		is_here && self.condition.as_ref().is_none_or(|condition| condition(local, state))
+	}
+}
+
+impl<P: ProgramState, T: Threading> fmt::Debug for Breakpoint<'_, P, T> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("Breakpoint")
+			.field("place", &self.place)
+			.field("is_conditional", &self.condition.is_some())
+			.finish()
+	}
+}
+
+/// A breakpoint condition which an interpreter with some [`Threading`](trait.Threading.html) can
+/// hold.
+///
+/// A [`Local`](struct.Local.html) interpreter holds any condition, and a
+/// [`Sendable`](struct.Sendable.html) one only those which are `Send`.
+pub trait IntoCondition<'a, P: ProgramState, T: Threading> {
+	/// Box the condition as the interpreter holds it.
+	fn into_condition(self) -> BoxedCondition<'a, P, T>;
+}
+
+impl<'a, P: ProgramState, F: Fn(&[P::Value], &P) -> bool + 'a> IntoCondition<'a, P, Local> for F {
+	fn into_condition(self) -> BoxedCondition<'a, P, Local> {
+		Box::new(self)
+	}
+}
+
+impl<'a, P: ProgramState, F: Fn(&[P::Value], &P) -> bool + Send + 'a> IntoCondition<'a, P, Sendable> for F {
+	fn into_condition(self) -> BoxedCondition<'a, P, Sendable> {
+		Box::new(self)
+	}
+}
+
+/// A change to the stack of a column which stops a program, right after the instruction which
+/// made it.
+///
+/// Add one with [`Interpreter::add_watchpoint`](struct.Interpreter.html#method.add_watchpoint).
+/// The stack is watched whether it's the local or the remote stack.
+///
+/// # Example
+///
+/// ```
+/// use col::interpreter::{ExecutionStatus, Interpreter, StopReason, Watchpoint};
+/// use col::program::SimpleProgramState;
+///
+/// let mut interpreter = Interpreter::<SimpleProgramState>::new("1~9^@\n1@", None, None);
+/// interpreter.add_watchpoint(Watchpoint::Push(1));
+///
+/// match interpreter.run_for(100) {
+///     ExecutionStatus::Stopped { reason: StopReason::Watchpoint { location, .. } } => {
+///         // the `^` at ip 3 moved the 9 to the stack of column 1
+///         assert_eq!(location.ip, 3);
+///     },
+///     status => panic!("The program didn't stop: {:?}", status),
+/// }
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Watchpoint {
+	/// A value is pushed to the stack of a column.
+	Push(u32),
+	/// A value is popped from the stack of a column, which popping an empty stack doesn't.
+	Pop(u32),
+	/// `c`, or `p` with output, empties the stack of a column which wasn't empty.
+	Clear(u32),
+	/// `s` swaps the stack of a column with another.
+	Swap(u32),
+	/// The stack of a column grows to more than this many values.
+	Depth(u32, usize),
+}
+
+impl Watchpoint {
+	fn fires(&self, instruction: Instruction, change: &Change, is_swap: bool) -> bool {
+		match *self {
+			Watchpoint::Push(column) => column == change.column && change.pushed > 0,
+			Watchpoint::Pop(column) => column == change.column && change.popped > 0,
+			Watchpoint::Clear(column) => {
+				let is_clearing = matches!(instruction, Instruction::Clear | Instruction::PrintAll);
+				is_clearing && column == change.column && change.before > 0 && change.after == 0
+			},
+			Watchpoint::Swap(column) => column == change.column && is_swap,
+			Watchpoint::Depth(column, depth) => column == change.column && change.before <= depth && change.after > depth,
+		}
+	}
+}
+
+/// How an instruction changed the stack of a column.
+pub(super) struct Change {
+	pub(super) column: u32,
+	/// How many values were popped
+	pub(super) popped: usize,
+	/// How many values were pushed
+	pub(super) pushed: usize,
+	/// How many values the stack had before
+	pub(super) before: usize,
+	/// How many values it has now
+	pub(super) after: usize,
+}
+
+/// The breakpoints and watchpoints of an interpreter.
+pub(super) struct Debugging<'a, P: ProgramState, T: Threading> {
+	breakpoints: Vec<(BreakpointId, Breakpoint<'a, P, T>)>,
+	watchpoints: Vec<(WatchpointId, Watchpoint)>,
+	/// The next identifier to give out, shared so they're all different
+	next_id: u32,
+	/// How many steps had been executed when a breakpoint last stopped the program, so resuming
+	/// doesn't stop at it again
+	stopped_at: Option<u64>,
+	/// Whether the last instruction was a `;`, so the next one is the first of the column
+	pub(super) is_entering: bool,
+}
+
+impl<P: ProgramState, T: Threading> Default for Debugging<'_, P, T> {
+	fn default() -> Self {
+		Debugging { breakpoints: Vec::new(), watchpoints: Vec::new(), next_id: 0, stopped_at: None, is_entering: false }
+	}
+}
+
+impl<'a, P: ProgramState, T: Threading> Debugging<'a, P, T> {
+	/// Whether the program can stop, so every step has to be executed on its own.
+	pub(super) fn is_active(&self) -> bool {
+		self.has_breakpoints() || self.is_watching()
+	}
+
+	pub(super) fn has_breakpoints(&self) -> bool {
+		!self.breakpoints.is_empty()
+	}
+
+	/// Whether the changes to the stacks have to be worked out.
+	pub(super) fn is_watching(&self) -> bool {
+		!self.watchpoints.is_empty()
+	}
+
+	pub(super) fn add_breakpoint(&mut self, breakpoint: Breakpoint<'a, P, T>) -> BreakpointId {
+		let id = BreakpointId(self.next_id());
+		self.breakpoints.push((id, breakpoint));
+		id
+	}
+
+	pub(super) fn remove_breakpoint(&mut self, id: BreakpointId) -> bool {
+		let len = self.breakpoints.len();
+		self.breakpoints.retain(|(other, _)| *other != id);
+		self.breakpoints.len() < len
+	}
+
+	pub(super) fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> WatchpointId {
+		let id = WatchpointId(self.next_id());
+		self.watchpoints.push((id, watchpoint));
+		id
+	}
+
+	pub(super) fn remove_watchpoint(&mut self, id: WatchpointId) -> bool {
+		let len = self.watchpoints.len();
+		self.watchpoints.retain(|(other, _)| *other != id);
+		self.watchpoints.len() < len
+	}
+
+	/// The breakpoint the program should stop at before executing an instruction, if any.
+	pub(super) fn breakpoint(&mut self, location: Location, instruction: Instruction, local: &[P::Value], state: &P, steps: u64) -> Option<StopReason> {
+		// the program is resuming from this breakpoint
+		if self.stopped_at == Some(steps) {
+			return None;
+		}
+
+		let id = self.breakpoints.iter()
+			.find(|(_, breakpoint)| breakpoint.matches(location, instruction, self.is_entering, local, state))
+			.map(|(id, _)| *id)?;
+
+		self.stopped_at = Some(steps);
+		Some(StopReason::Breakpoint { id, location })
+	}
+
+	/// The watchpoint which an executed instruction set off by changing the stacks, if any.
+	pub(super) fn watchpoint(&self, location: Location, instruction: Instruction, changes: &[Change]) -> Option<StopReason> {
+		// without a separate remote stack, `s` doesn't swap anything
+		let is_swap = instruction == Instruction::SwapStacks && changes.len() > 1;
+
+		self.watchpoints.iter()
+			.find(|(_, watchpoint)| changes.iter().any(|change| watchpoint.fires(instruction, change, is_swap)))
+			.map(|(id, _)| StopReason::Watchpoint { id: *id, location })
+	}
+
+	fn next_id(&mut self) -> u32 {
+		self.next_id += 1;
+		self.next_id - 1
+	}
+}
@@ -0,0 +1,374 @@
+//! The bytecode backend.
+//!
+//! Each column is compiled to a list of ops, where common sequences of instructions are fused into
+//! superinstructions. Fused sequences never contain the target of a jump, so every jump lands at
+//! the start of an op.
+
+use std::cell::RefMut;
+
+use super::{execute_binary, execute_stack_instruction, stack_cells, Interpreter, StepResponse, Threading};
+use crate::error::{Error, Location};
+use crate::parser::{Column, Instruction, Program};
+use crate::program::{ProgramState, VecStack};
+use crate::value::Value;
+
+/// A compiled program.
+pub(super) struct Bytecode {
+	columns: Vec<CompiledColumn>,
+}
+
+struct CompiledColumn {
+	ops: Vec<Code>,
+	/// The op starting at each instruction pointer, if any
+	entries: Vec<Option<u32>>,
+}
+
+/// An op along with where it came from.
+#[derive(Copy, Clone, Debug)]
+struct Code {
+	op: Op,
+	/// Instruction pointer of the first instruction of the op
+	ip: u32,
+	/// Number of steps the op is worth
+	steps: u32,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Op {
+	/// Any instruction without a more specific op
+	Instruction(Instruction),
+	/// `[`, jumping to op `target` if the top value is zero
+	JumpIfZero(u32),
+	/// `]`, jumping to op `target` if the top value is non-zero
+	JumpIfNonZero(u32),
+	/// `"`, pushing the string of the instruction at `ip` and then continuing at op `target`
+	String(u32),
+	/// A value followed by a binary instruction, e.g. `1+`
+	ValueBinary(u32, Instruction),
+	/// `:^`, copying the top value to the remote stack
+	DuplicateToRemote,
+	/// `v` followed by a value and `~`, e.g. `v0~`
+	MoveToLocalSetRemote(u32),
+}
+
+/// What to do after an op.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Flow {
+	/// Keep executing ops of the same column
+	Next,
+	/// The columns were changed at the location, so the stacks have to be resolved again
+	Switch(Location),
+	/// The program has terminated
+	Terminate,
+	/// The op could fail or reach a limit part way through, so its instructions have to be
+	/// executed one at a time with direct steps instead, which fail where they should
+	Direct,
+}
+
+impl Bytecode {
+	pub(super) fn compile(program: &Program) -> Bytecode {
+		Bytecode {
+			columns: program.columns().iter().map(CompiledColumn::compile).collect(),
+		}
+	}
+}
+
+impl CompiledColumn {
+	fn compile(column: &Column) -> CompiledColumn {
+		let instructions = column.instructions();
+		let len = instructions.len();
+
+		// sequences can't be fused across anything that can be jumped to
+		let mut is_target = vec![false; len];
+
+		for (ip, instr) in instructions.iter().enumerate() {
+			match instr {
+				Instruction::LeftBracket | Instruction::RightBracket | Instruction::StringMode => {
+					is_target[column.jump(ip as u32) as usize] = true;
+				},
+				_ => {},
+			}
+		}
+
+		let fusable = |ip: usize, count: usize| ip + count <= len && !is_target[ip + 1..ip + count].iter().any(|t| *t);
+
+		let mut ops = Vec::new();
+		let mut entries = vec![None; len];
+		let mut ip = 0;
+
+		while ip < len {
+			let rest = &instructions[ip..];
+
+			let (op, steps) = match rest {
+				[Instruction::Value(value), binary, ..] if is_binary(*binary) && fusable(ip, 2) => {
+					(Op::ValueBinary(*value, *binary), 2)
+				},
+				[Instruction::DuplicateTop, Instruction::MoveToRemote, ..] if fusable(ip, 2) => {
+					(Op::DuplicateToRemote, 2)
+				},
+				[Instruction::MoveToLocal, Instruction::Value(value), Instruction::SetRemoteStack, ..] if fusable(ip, 3) => {
+					(Op::MoveToLocalSetRemote(*value), 3)
+				},
+				// jump targets are resolved to ops below
+				[Instruction::LeftBracket, ..] => (Op::JumpIfZero(column.jump(ip as u32)), 1),
+				[Instruction::RightBracket, ..] => (Op::JumpIfNonZero(column.jump(ip as u32)), 1),
+				[Instruction::StringMode, ..] => {
+					// pushing the string is one step, then one for every character
+					(Op::String(column.jump(ip as u32)), 1 + column.string(ip as u32).len() as u32)
+				},
+				[instr, ..] => (Op::Instruction(*instr), 1),
+				[] => unreachable!(),
+			};
+
+			entries[ip] = Some(ops.len() as u32);
+			ops.push(Code { op, ip: ip as u32, steps });
+
+			// fused instructions only take up one op
+			ip += match op {
+				Op::ValueBinary(..) | Op::DuplicateToRemote => 2,
+				Op::MoveToLocalSetRemote(_) => 3,
+				_ => 1,
+			};
+		}
+
+		// turn jump targets from instruction pointers into ops
+		for code in &mut ops {
+			match &mut code.op {
+				Op::JumpIfZero(target) | Op::JumpIfNonZero(target) | Op::String(target) => {
+					*target = entries[*target as usize].expect("jump target in the middle of an op");
+				},
+				_ => {},
+			}
+		}
+
+		CompiledColumn { ops, entries }
+	}
+}
+
+fn is_binary(instruction: Instruction) -> bool {
+	matches!(instruction, Instruction::Add
+		| Instruction::Subtract
+		| Instruction::Multiply
+		| Instruction::Divide
+		| Instruction::Modulo
+		| Instruction::Equals
+		| Instruction::GreaterThan
+		| Instruction::BitwiseNand
+		| Instruction::LogicalAnd
+		| Instruction::LogicalOr)
+}
+
+impl<'a, P: ProgramState, T: Threading> Interpreter<'a, P, T> {
+	/// Execute up to `budget` steps using the bytecode backend.
+	///
+	/// Zero steps are executed if the interpreter is somewhere bytecode can't start from, such as
+	/// in the middle of a string or a fused op, or at an op which has to be executed one
+	/// instruction at a time, in which case a direct step should be used instead.
+	pub(super) fn run_bytecode(&mut self, budget: u32) -> Result<StepResponse, Error> {
+		let mut response = StepResponse { steps: 0, ..StepResponse::default() };
+
+		if self.is_string_mode {
+			return Ok(response);
+		}
+
+		if self.bytecode.is_none() {
+			self.bytecode = Some(Bytecode::compile(&self.program));
+		}
+
+		let Interpreter { program, bytecode, io, state, local_column, remote_column, ip, rules, limits, cells, random, deterministic, end_of_input, steps, .. } = self;
+		let rules = *rules;
+		let bytecode = bytecode.as_ref().unwrap();
+		let program_len = program.len();
+
+		// the stacks only have to be checked after every op if they are limited
+		let check_stacks = limits.max_stack_depth.is_some() || limits.max_cells.is_some();
+
+		loop {
+			let column = &bytecode.columns[*local_column as usize];
+			let source = &program.columns()[*local_column as usize];
+
+			let mut pc = match column.entries.get(*ip as usize) {
+				Some(Some(pc)) => *pc,
+				_ => return Ok(response),
+			};
+
+			// where the columns were changed
+			let switched = {
+				// resolve the stacks once, until the columns change
+				let mut local_stack: RefMut<VecStack<P::Value>> = state.nth(*local_column).unwrap().borrow_mut();
+				let mut remote_stack: Option<RefMut<VecStack<P::Value>>> = state.nth(*remote_column)
+					.filter(|_| local_column != remote_column) // avoid a BorrowMutError
+					.map(|v| v.borrow_mut());
+
+				let mut cells_before = stack_cells(&local_stack, remote_stack.as_deref());
+
+				loop {
+					let code = column.ops[pc as usize];
+
+					if response.steps + code.steps > budget {
+						settle_cells(cells, &mut cells_before, &local_stack, remote_stack.as_deref());
+						*ip = code.ip;
+						return Ok(response);
+					}
+
+					response.steps += code.steps;
+					pc = (pc + 1) % column.ops.len() as u32;
+
+					let location = Location { column: *local_column, ip: code.ip };
+
+					// an op pushing several values, or a fused op pushing one before it pops, is only
+					// executed at once if none of them can reach a limit, and the values are counted
+					// after every op when they are limited
+					let total = *cells;
+					let has_room = |local_stack: &VecStack<P::Value>, remote_stack: Option<&VecStack<P::Value>>, count: usize| {
+						let depth = local_stack.len().max(remote_stack.map_or(0, VecStack::len));
+
+						limits.max_stack_depth.is_none_or(|max| depth + count <= max)
+							&& limits.max_cells.is_none_or(|max| total + count <= max)
+					};
+
+					let flow = (|| {
+						let mut flow = Flow::Next;
+
+						match code.op {
+							Op::JumpIfZero(target) => {
+								if local_stack.peek().is_zero() {
+									pc = target;
+								}
+							},
+							Op::JumpIfNonZero(target) => {
+								if !local_stack.peek().is_zero() {
+									pc = target;
+								}
+							},
+							Op::String(target) => {
+								if !has_room(&local_stack, remote_stack.as_deref(), code.steps as usize - 1) {
+									return Ok(Flow::Direct);
+								}
+
+								for c in source.string(code.ip) {
+									local_stack.push(P::Value::from_char(*c));
+								}
+								pc = target;
+							},
+							Op::ValueBinary(a, instr) => {
+								let a = P::Value::from_u32(a);
+								let is_empty = local_stack.is_empty() && rules.strict.empty_local_stack;
+
+								// the binary instruction comes right after the value, and only pops once
+								// it's known not to fail
+								match execute_binary(instr, &a, &local_stack.peek(), rules) {
+									Ok(value) if !is_empty && has_room(&local_stack, remote_stack.as_deref(), 1) => {
+										local_stack.try_pop();
+										local_stack.push(value);
+									},
+									_ => return Ok(Flow::Direct),
+								}
+							},
+							Op::DuplicateToRemote => {
+								if rules.check_remote(&remote_stack).is_err() || !has_room(&local_stack, remote_stack.as_deref(), 1) {
+									return Ok(Flow::Direct);
+								}
+
+								let value = local_stack.peek();
+
+								// `^` does nothing without a separate remote stack, so the copy stays
+								match &mut remote_stack {
+									Some(remote_stack) => remote_stack.push(value),
+									None => local_stack.push(value),
+								}
+							},
+							Op::MoveToLocalSetRemote(value) => {
+								if !has_room(&local_stack, remote_stack.as_deref(), 2) {
+									return Ok(Flow::Direct);
+								}
+
+								// `v` is the first instruction, so failing there leaves nothing to undo
+								rules.check_remote(&remote_stack).map_err(|trap| trap.at(location))?;
+
+								if let Some(remote_stack) = &mut remote_stack {
+									local_stack.push(rules.pop_remote(remote_stack).map_err(|trap| trap.at(location))?);
+								}
+
+								*remote_column = value;
+								*ip = column.ops[pc as usize].ip;
+								flow = Flow::Switch(Location { ip: code.ip + 2, ..location });
+							},
+							Op::Instruction(Instruction::SetLocalColumn) => {
+								*local_column = rules.pop_local(&mut local_stack).map_err(|trap| trap.at(location))?.to_u32() % program_len;
+								*ip = 0;
+								flow = Flow::Switch(location);
+							},
+							Op::Instruction(Instruction::SetRemoteStack) => {
+								*remote_column = rules.pop_local(&mut local_stack).map_err(|trap| trap.at(location))?.to_u32();
+								*ip = column.ops[pc as usize].ip;
+								flow = Flow::Switch(location);
+							},
+							Op::Instruction(Instruction::Terminate) => {
+								*ip = column.ops[pc as usize].ip;
+								flow = Flow::Terminate;
+							},
+							Op::Instruction(instr @ Instruction::Input)
+							| Op::Instruction(instr @ Instruction::PrintChar)
+							| Op::Instruction(instr @ Instruction::PrintNumber)
+							| Op::Instruction(instr @ Instruction::PrintAll) => {
+								io.execute(instr, &mut local_stack, rules, end_of_input, location, None)?;
+							},
+							Op::Instruction(Instruction::Random) => {
+								local_stack.push(random.next(*deterministic).ok_or(Error::ReplayEnded { location })?);
+							},
+							Op::Instruction(instr) => {
+								execute_stack_instruction(instr, *local_column, &mut local_stack, remote_stack.as_deref_mut(), rules)
+									.map_err(|trap| trap.at(location))?;
+							},
+						}
+
+						// keep count of the values in every stack
+						if check_stacks || flow != Flow::Next {
+							settle_cells(cells, &mut cells_before, &local_stack, remote_stack.as_deref());
+							limits.check_stacks(&local_stack, remote_stack.as_deref(), *cells).map_err(|limit| limit.at(location))?;
+						}
+
+						Ok(flow)
+					})();
+
+					match flow {
+						Ok(Flow::Next) => {},
+						Ok(Flow::Direct) => {
+							response.steps -= code.steps;
+							settle_cells(cells, &mut cells_before, &local_stack, remote_stack.as_deref());
+							*ip = code.ip;
+							return Ok(response);
+						},
+						Ok(Flow::Switch(location)) => break location,
+						Ok(Flow::Terminate) => {
+							response.is_alive = false;
+							return Ok(response);
+						},
+						Err(error) => {
+							// stay at the op which failed, keeping everything before it
+							settle_cells(cells, &mut cells_before, &local_stack, remote_stack.as_deref());
+							*ip = code.ip;
+							*steps += (response.steps - code.steps) as u64;
+							return Err(error);
+						},
+					}
+				}
+			};
+
+			// the new remote stack has to exist before it is resolved
+			if *remote_column >= program_len {
+				limits.init_stack(state, program_len, *remote_column).map_err(|limit| limit.at(switched))?;
+			}
+		}
+	}
+}
+
+/// Update the number of values in every stack, from how many the local and remote stacks held
+/// since it was last updated.
+fn settle_cells<V: Value>(cells: &mut usize, before: &mut usize, local_stack: &VecStack<V>, remote_stack: Option<&VecStack<V>>) {
+	let after = stack_cells(local_stack, remote_stack);
+
+	*cells = (*cells + after).saturating_sub(*before);
+	*before = after;
+}
@@ -0,0 +1,54 @@
+//! Stopping a running interpreter from outside.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A handle which stops a running interpreter, even from another thread.
+///
+/// Every clone of a handle shares the same flag, so one can be given to the interpreter with
+/// [`Interpreter::with_cancellation`](struct.Interpreter.html#method.with_cancellation) and
+/// another kept to cancel it. The program is then stopped with
+/// [`Error::Cancelled`](../error/enum.Error.html).
+///
+/// # Example
+///
+/// ```
+/// use std::thread;
+///
+/// use col::interpreter::{CancellationHandle, Interpreter};
+/// use col::program::SimpleProgramState;
+///
+/// let handle = CancellationHandle::new();
+/// let cancellation = handle.clone();
+///
+/// // never terminates
+/// let running = thread::spawn(move || {
+///     Interpreter::<SimpleProgramState>::new("1[]@", None, None)
+///         .with_cancellation(cancellation)
+///         .run()
+/// });
+///
+/// handle.cancel();
+/// assert!(running.join().unwrap().is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancellationHandle {
+	cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationHandle {
+	/// Create a handle which hasn't been cancelled yet.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Stop every interpreter using this handle as soon as possible.
+	pub fn cancel(&self) {
+		self.cancelled.store(true, Ordering::Relaxed);
+	}
+
+	/// Whether or not the handle has been cancelled.
+	pub fn is_cancelled(&self) -> bool {
+		self.cancelled.load(Ordering::Relaxed)
+	}
+}
@@ -0,0 +1,152 @@
+//! How characters are read from input and written to output.
+
+use crate::value::Value;
+
+/// What is pushed for input which isn't valid UTF-8.
+const REPLACEMENT: u32 = 0xfffd;
+
+/// How the characters of `_`, `$` and `p` are encoded as bytes.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Charset {
+	/// Every character is a Unicode scalar value encoded as UTF-8. Input which isn't valid UTF-8
+	/// is read as U+FFFD, the replacement character.
+	#[default]
+	Utf8,
+	/// Every character is a single byte, which is its code point. Values above 255 can't be
+	/// printed.
+	Latin1,
+	/// Every character is a single byte. Values are printed as their lowest byte, so they can
+	/// always be printed.
+	Bytes,
+}
+
+/// What happens when `$` or `p` print a value which isn't a character in the
+/// [charset](enum.Charset.html).
+///
+/// Unless one is set, `$` stops the program with an error, and `p` prints nothing for the value.
+///
+/// # Example
+///
+/// ```
+/// use col::interpreter::{Interpreter, Unprintable};
+/// use col::program::SimpleProgramState;
+///
+/// let mut output = Vec::new();
+///
+/// // 0xFFFFFF is past the last code point
+/// Interpreter::<SimpleProgramState>::new("FF*F*F*F*F*F*:$\"ok\"p@", None, Some(&mut output))
+///     .with_unprintable(Unprintable::Skip)
+///     .run()
+///     .expect("The program failed");
+///
+/// assert_eq!(output, b"ko");
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Unprintable {
+	/// Stop the program with an [`InvalidCodePoint`](../error/enum.Error.html) error.
+	Error,
+	/// Print a replacement instead, which is U+FFFD in UTF-8 and `?` in Latin-1.
+	Replace,
+	/// Print nothing.
+	Skip,
+}
+
+impl Charset {
+	/// The character a value is printed as, if it is one.
+	pub(super) fn to_char<V: Value>(self, value: &V) -> Option<char> {
+		match self {
+			Charset::Utf8 => value.to_char(),
+			Charset::Latin1 => value.to_char().filter(|c| (*c as u32) < 256),
+			Charset::Bytes => Some(value.to_u32() as u8 as char),
+		}
+	}
+
+	/// What is printed instead of values which aren't characters.
+	pub(super) fn replacement(self) -> char {
+		match self {
+			Charset::Utf8 => std::char::REPLACEMENT_CHARACTER,
+			Charset::Latin1 | Charset::Bytes => '?',
+		}
+	}
+
+	/// Encode printed text, where every character has come from [`to_char`](#method.to_char).
+	pub(super) fn encode(self, text: &str, output: &mut Vec<u8>) {
+		match self {
+			Charset::Utf8 => output.extend_from_slice(text.as_bytes()),
+			// every character is below 256
+			Charset::Latin1 | Charset::Bytes => output.extend(text.chars().map(|c| c as u8)),
+		}
+	}
+}
+
+/// Turns input bytes into characters, keeping the bytes of a character read so far in case the
+/// rest of it isn't available yet.
+#[derive(Debug, Default)]
+pub(super) struct Decoder {
+	/// The start of a UTF-8 sequence
+	pub(super) partial: Vec<u8>,
+	/// A byte which was read, but belongs to the next character
+	pub(super) peeked: Option<u8>,
+}
+
+impl Decoder {
+	/// Take the byte which was read too early, if any.
+	pub(super) fn take_peeked(&mut self) -> Option<u8> {
+		self.peeked.take()
+	}
+
+	/// Add the next byte of input, or `None` at the end of the input.
+	///
+	/// Once a whole character has been read, the result is its code point, or `None` at the end of
+	/// the input.
+	pub(super) fn push(&mut self, charset: Charset, byte: Option<u8>) -> Option<Option<u32>> {
+		let byte = match (charset, byte) {
+			(Charset::Latin1, byte) | (Charset::Bytes, byte) => return Some(byte.map(u32::from)),
+			(Charset::Utf8, Some(byte)) => byte,
+			// the input ended in the middle of a character
+			(Charset::Utf8, None) if !self.partial.is_empty() => {
+				self.partial.clear();
+				return Some(Some(REPLACEMENT));
+			},
+			(Charset::Utf8, None) => return Some(None),
+		};
+
+		if self.partial.is_empty() {
+			match sequence_len(byte) {
+				Some(1) => return Some(Some(byte as u32)),
+				None => return Some(Some(REPLACEMENT)),
+				_ => {},
+			}
+		} else if byte & 0xc0 != 0x80 {
+			// not a continuation byte, so the character was cut short and this one starts the next
+			self.partial.clear();
+			self.peeked = Some(byte);
+			return Some(Some(REPLACEMENT));
+		}
+
+		self.partial.push(byte);
+
+		if Some(self.partial.len()) != sequence_len(self.partial[0]) {
+			return None;
+		}
+
+		// overlong sequences and surrogates are still invalid
+		let c = std::str::from_utf8(&self.partial).ok()
+			.and_then(|s| s.chars().next())
+			.map_or(REPLACEMENT, |c| c as u32);
+
+		self.partial.clear();
+		Some(Some(c))
+	}
+}
+
+/// How many bytes a UTF-8 sequence starting with the byte has, if it can start one.
+fn sequence_len(byte: u8) -> Option<usize> {
+	match byte {
+		0x00..=0x7f => Some(1),
+		0xc2..=0xdf => Some(2),
+		0xe0..=0xef => Some(3),
+		0xf0..=0xf4 => Some(4),
+		_ => None,
+	}
+}
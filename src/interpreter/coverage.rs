@@ -0,0 +1,333 @@
+//! Finding out which parts of a program were executed.
+
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+
+use super::Observer;
+use crate::parser::{Column, Instruction, Program};
+use crate::value::Value;
+
+/// An [observer](trait.Observer.html) recording which instructions of a program were executed,
+/// which way every `[` went, and so which columns were never entered.
+///
+/// The same collector can observe any number of runs of the program, adding them all up. The
+/// coverage can also be saved as an lcov file with [`write_lcov`](#method.write_lcov), and added
+/// to again later with [`merge_lcov`](#method.merge_lcov), so it adds up across separate runs too.
+///
+/// # Example
+///
+/// ```
+/// use col::interpreter::{Coverage, Interpreter};
+/// use col::parser;
+/// use col::program::SimpleProgramState;
+///
+/// let program = parser::parse("_[1;]@\n\"not reached\"@");
+/// let mut coverage = Coverage::new(&program);
+///
+/// Interpreter::<SimpleProgramState>::from_program(&program, None, None)
+///     .with_observer(&mut coverage)
+///     .run()
+///     .expect("The program failed");
+///
+/// // there was no input, so the loop was skipped
+/// assert_eq!(coverage.branch(0, 1), Some((0, 1)));
+/// assert_eq!(coverage.count(0, 2), 0);
+/// assert!(!coverage.is_entered(1));
+/// ```
+#[derive(Clone, Debug)]
+pub struct Coverage {
+	program: Program,
+	/// Which instructions are code, leaving out the characters inside strings, by column
+	code: Vec<Vec<bool>>,
+	/// How many times each instruction of each column was executed
+	counts: Vec<Vec<u64>>,
+	/// How many times each `[` went into the loop and skipped it, by instruction
+	branches: Vec<Vec<(u64, u64)>>,
+	/// The `[` whose branch is known once it has executed
+	pending: Option<(u32, u32)>,
+}
+
+impl Coverage {
+	/// Create a collector for a program, with nothing executed yet.
+	pub fn new(program: &Program) -> Coverage {
+		let sizes = || program.columns().iter().map(|column| column.len() as usize);
+
+		Coverage {
+			program: program.clone(),
+			code: program.columns().iter().map(Column::code).collect(),
+			counts: sizes().map(|len| vec![0; len]).collect(),
+			branches: sizes().map(|len| vec![(0, 0); len]).collect(),
+			pending: None,
+		}
+	}
+
+	/// How many times the instruction at `ip` in a column was executed.
+	///
+	/// Every character pushed in string mode counts for the `"` which started the string.
+	pub fn count(&self, column: u32, ip: u32) -> u64 {
+		self.counts.get(column as usize)
+			.and_then(|counts| counts.get(ip as usize))
+			.copied()
+			.unwrap_or(0)
+	}
+
+	/// How many times the `[` at `ip` in a column went into the loop, and how many times it skipped
+	/// it, or `None` if it isn't a `[`.
+	pub fn branch(&self, column: u32, ip: u32) -> Option<(u64, u64)> {
+		match self.program.column(column)?.instruction(ip)? {
+			Instruction::LeftBracket => Some(self.branches[column as usize][ip as usize]),
+			_ => None,
+		}
+	}
+
+	/// Whether the instruction at `ip` in a column is code rather than a character inside a string,
+	/// or was executed anyway.
+	///
+	/// Code which can never be reached, such as after a `@`, still counts, so it shows up as never
+	/// executed.
+	pub fn is_code(&self, column: u32, ip: u32) -> bool {
+		let is_code = self.code.get(column as usize)
+			.and_then(|code| code.get(ip as usize))
+			.copied()
+			.unwrap_or(false);
+
+		is_code || self.count(column, ip) > 0
+	}
+
+	/// Whether any instruction of a column was executed.
+	pub fn is_entered(&self, column: u32) -> bool {
+		self.counts.get(column as usize).is_some_and(|counts| counts.iter().any(|count| *count > 0))
+	}
+
+	/// Write the coverage in the lcov format, for the source file at `path`.
+	///
+	/// Every column is a line, counted as many times as its most executed instruction, leaving out
+	/// characters in strings. Each `[` is
+	/// a block of two branches, numbered by its character offset, the first going into the loop
+	/// and the second skipping it. Every instruction also has a record of its own,
+	/// `CA:<line>,<offset>,<count>`, which other lcov tools ignore.
+	pub fn write_lcov<W: Write>(&self, path: &str, mut writer: W) -> io::Result<()> {
+		writeln!(writer, "TN:")?;
+		writeln!(writer, "SF:{}", path)?;
+
+		let (mut found, mut hit) = (0, 0);
+
+		for (line, column) in self.lines() {
+			for ip in 0..column.len() {
+				if let Some((entered, skipped)) = self.branch(line - 1, ip) {
+					let offset = column.offset(ip).unwrap();
+					let executed = self.count(line - 1, ip) > 0;
+
+					for (branch, count) in [entered, skipped].iter().enumerate() {
+						if executed {
+							writeln!(writer, "BRDA:{},{},{},{}", line, offset, branch, count)?;
+						} else {
+							writeln!(writer, "BRDA:{},{},{},-", line, offset, branch)?;
+						}
+
+						found += 1;
+						hit += (*count > 0) as u32;
+					}
+				}
+			}
+		}
+
+		writeln!(writer, "BRF:{}", found)?;
+		writeln!(writer, "BRH:{}", hit)?;
+
+		let (mut found, mut hit) = (0, 0);
+
+		for (line, column) in self.lines() {
+			let counts = &self.counts[line as usize - 1];
+			let count = counts.iter().max().copied().unwrap_or(0);
+
+			writeln!(writer, "DA:{},{}", line, count)?;
+
+			for (ip, count) in counts.iter().enumerate() {
+				if self.is_code(line - 1, ip as u32) {
+					writeln!(writer, "CA:{},{},{}", line, column.offset(ip as u32).unwrap(), count)?;
+				}
+			}
+
+			found += 1;
+			hit += (count > 0) as u32;
+		}
+
+		writeln!(writer, "LF:{}", found)?;
+		writeln!(writer, "LH:{}", hit)?;
+		writeln!(writer, "end_of_record")
+	}
+
+	/// Add the coverage of an lcov file written by [`write_lcov`](#method.write_lcov) for the same
+	/// program.
+	///
+	/// Fails without adding anything if the file has an instruction or branch where the program
+	/// doesn't.
+	pub fn merge_lcov<R: Read>(&mut self, reader: R) -> io::Result<()> {
+		let mut merged = self.clone();
+
+		for line in BufReader::new(reader).lines() {
+			let line = line?;
+
+			let (record, values) = match line.find(':') {
+				Some(index) => (&line[..index], &line[index + 1..]),
+				None => continue,
+			};
+
+			let values: Vec<&str> = values.split(',').collect();
+
+			match (record, values.as_slice()) {
+				("CA", [line, offset, count]) => {
+					let (column, ip) = merged.find(line, offset)?;
+					merged.counts[column][ip] += parse::<u64>(count)?;
+				},
+				("BRDA", [line, offset, branch, count]) => {
+					let (column, ip) = merged.find(line, offset)?;
+
+					if merged.program.columns()[column].instructions()[ip] != Instruction::LeftBracket {
+						return Err(mismatch());
+					}
+
+					// a branch of an instruction which was never executed wasn't taken
+					let count = if *count == "-" { 0 } else { parse(count)? };
+					let branches = &mut merged.branches[column][ip];
+
+					match *branch {
+						"0" => branches.0 += count,
+						"1" => branches.1 += count,
+						_ => return Err(mismatch()),
+					}
+				},
+				// everything else follows from the instructions and branches
+				_ => {},
+			}
+		}
+
+		*self = merged;
+		Ok(())
+	}
+
+	/// Write the source of every column, marking what wasn't executed, followed by a summary.
+	///
+	/// Under every line, `!` marks an instruction which was never executed, and `?` a `[` which
+	/// was executed but only ever went one way. Characters in strings aren't instructions.
+	pub fn write_report<W: Write>(&self, mut writer: W) -> io::Result<()> {
+		let mut instructions = (0, 0);
+		let mut branches = (0, 0);
+		let mut columns = (0, 0);
+		let mut never_entered = Vec::new();
+
+		for (line, column) in self.lines() {
+			let index = line - 1;
+			// as wide as the whole line, so the marks line up with the source
+			let mut marks: Vec<char> = column.indent(column.source().chars().count() as u32).chars().collect();
+
+			for ip in (0..column.len()).filter(|ip| self.is_code(index, *ip)) {
+				let count = self.count(index, ip);
+				let offset = column.offset(ip).unwrap() as usize;
+
+				instructions.1 += 1;
+
+				if count == 0 {
+					marks[offset] = '!';
+				} else {
+					instructions.0 += 1;
+				}
+
+				if let Some((entered, skipped)) = self.branch(index, ip) {
+					branches.1 += 2;
+					branches.0 += (entered > 0) as u32 + (skipped > 0) as u32;
+
+					if count > 0 && (entered == 0 || skipped == 0) {
+						marks[offset] = '?';
+					}
+				}
+			}
+
+			columns.1 += 1;
+
+			if self.is_entered(index) {
+				columns.0 += 1;
+			} else {
+				never_entered.push(index.to_string());
+			}
+
+			let number = format!("{:>4}", index);
+			writeln!(writer, "{} | {}", number, column.source())?;
+
+			let marks: String = marks.into_iter().collect();
+
+			if !marks.trim().is_empty() {
+				writeln!(writer, "{} | {}", " ".repeat(number.len()), marks.trim_end())?;
+			}
+		}
+
+		let percent = |(hit, found): (u32, u32)| if found == 0 { 100.0 } else { hit as f64 * 100.0 / found as f64 };
+
+		writeln!(writer)?;
+		writeln!(writer, "instructions executed: {} of {} ({:.1}%)", instructions.0, instructions.1, percent(instructions))?;
+		writeln!(writer, "branches taken: {} of {} ({:.1}%)", branches.0, branches.1, percent(branches))?;
+		writeln!(writer, "columns entered: {} of {} ({:.1}%)", columns.0, columns.1, percent(columns))?;
+
+		if !never_entered.is_empty() {
+			writeln!(writer, "columns never entered: {}", never_entered.join(", "))?;
+		}
+
+		Ok(())
+	}
+
+	/// The columns which have instructions, along with their line numbers, starting from one.
+	fn lines(&self) -> impl Iterator<Item = (u32, &Column)> {
+		self.program.columns().iter().enumerate()
+			.filter(|(_, column)| !column.is_empty())
+			.map(|(index, column)| (index as u32 + 1, column))
+	}
+
+	/// The column and instruction pointer of the instruction at a line and character offset.
+	fn find(&self, line: &str, offset: &str) -> io::Result<(usize, usize)> {
+		let line: usize = parse(line)?;
+		let offset: u32 = parse(offset)?;
+
+		let column = line.checked_sub(1)
+			.and_then(|index| self.program.column(index as u32))
+			.ok_or_else(mismatch)?;
+
+		let ip = (0..column.len())
+			.find(|ip| column.offset(*ip) == Some(offset))
+			.ok_or_else(mismatch)?;
+
+		Ok((line - 1, ip as usize))
+	}
+}
+
+impl<V: Value> Observer<V> for Coverage {
+	fn before_instruction(&mut self, column: u32, ip: u32, instruction: Instruction) {
+		if let Some(count) = self.counts.get_mut(column as usize).and_then(|counts| counts.get_mut(ip as usize)) {
+			*count += 1;
+
+			if instruction == Instruction::LeftBracket {
+				self.pending = Some((column, ip));
+			}
+		}
+	}
+
+	fn after_instruction(&mut self, local: &[V], _remote: Option<&[V]>) {
+		// `[` doesn't pop, so the value it looked at is still there
+		if let Some((column, ip)) = self.pending.take() {
+			let branches = &mut self.branches[column as usize][ip as usize];
+
+			if local.last().is_none_or(V::is_zero) {
+				branches.1 += 1;
+			} else {
+				branches.0 += 1;
+			}
+		}
+	}
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> io::Result<T> {
+	value.parse().map_err(|_| io::Error::new(ErrorKind::InvalidData, "invalid coverage file"))
+}
+
+fn mismatch() -> io::Error {
+	io::Error::new(ErrorKind::InvalidData, "the coverage file doesn't match the program")
+}
@@ -0,0 +1,80 @@
+//! Input pushed by the host and output collected as events, so a program can be driven from an
+//! event loop without blocking.
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, ErrorKind, Read};
+
+/// Something a program printed.
+///
+/// Collect these with
+/// [`Interpreter::with_output_events`](struct.Interpreter.html#method.with_output_events)
+/// instead of writing them to a writer.
+///
+/// # Example
+///
+/// ```
+/// use col::interpreter::{ExecutionStatus, Interpreter, OutputEvent};
+/// use col::program::SimpleProgramState;
+///
+/// let mut interpreter = Interpreter::<SimpleProgramState>::new("_:#$@", None, None)
+///     .with_suspending_input()
+///     .with_output_events();
+///
+/// // there is no input yet, so the program stops at `_`
+/// assert!(matches!(interpreter.run_for(100), ExecutionStatus::NeedsInput));
+///
+/// interpreter.push_input(b"a");
+/// assert!(matches!(interpreter.run_for(100), ExecutionStatus::Terminated));
+/// assert_eq!(interpreter.take_output(), vec![OutputEvent::Number(String::from("97")), OutputEvent::Char('a')]);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OutputEvent {
+	/// `$` printed a character.
+	///
+	/// With the [Latin-1 or bytes charsets](enum.Charset.html), every character is below 256 and
+	/// stands for a single byte, as do those printed by `p`.
+	Char(char),
+	/// `#` printed a value as a decimal number.
+	Number(String),
+	/// `p` printed the whole stack as a string.
+	String(String),
+}
+
+impl fmt::Display for OutputEvent {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			OutputEvent::Char(c) => write!(f, "{}", c),
+			OutputEvent::Number(s) | OutputEvent::String(s) => f.write_str(s),
+		}
+	}
+}
+
+/// Input pushed by the host, which asks for more instead of blocking when it runs out.
+#[derive(Debug, Default)]
+pub(super) struct PushedInput {
+	pub(super) buffer: VecDeque<u8>,
+	/// Whether the host said there will be no more input
+	pub(super) is_closed: bool,
+}
+
+impl PushedInput {
+	pub(super) fn push(&mut self, bytes: &[u8]) {
+		self.buffer.extend(bytes);
+	}
+
+	pub(super) fn close(&mut self) {
+		self.is_closed = true;
+	}
+}
+
+impl Read for PushedInput {
+	fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+		if self.buffer.is_empty() && !self.is_closed {
+			return Err(ErrorKind::WouldBlock.into());
+		}
+
+		// reads nothing once closed and empty, which is the end of the input
+		self.buffer.read(buf)
+	}
+}
@@ -0,0 +1,175 @@
+//! Handling the input and output of a program.
+
+use std::io;
+
+/// What `_` read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Input {
+	/// A character, pushed as its code point.
+	Char(u32),
+	/// A decimal integer, pushed as its value. Zero is pushed if it doesn't fit the value type.
+	Number(String),
+	/// There is no more input, so the
+	/// [end of input value](struct.Interpreter.html#method.with_end_of_input) is pushed.
+	End,
+}
+
+/// How `_` reads the input of the reader.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum InputMode {
+	/// Read a character as soon as it's available.
+	#[default]
+	Char,
+	/// Wait for a whole line, or the end of the input, before reading its characters one at a time.
+	Line,
+	/// Read a decimal integer, optionally negative, skipping anything before it which isn't part
+	/// of a number. The character after the number is skipped as well.
+	Number,
+}
+
+/// Handles the input and output instructions of a program, `_`, `$`, `#` and `p`.
+///
+/// By default, an interpreter uses its reader and writer, in the
+/// [input mode](enum.InputMode.html) and [charset](enum.Charset.html) it was given. A handler set
+/// with [`Interpreter::with_io_handler`](struct.Interpreter.html#method.with_io_handler) replaces
+/// all of that, as well as replays, pushed input and output events.
+///
+/// Returning a [`WouldBlock`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock)
+/// error from [`read`](#tymethod.read) makes the interpreter wait for input, and any other error
+/// stops the program.
+///
+/// # Example
+///
+/// ```
+/// use std::io;
+///
+/// use col::interpreter::{Input, Interpreter, IoHandler};
+/// use col::program::SimpleProgramState;
+///
+/// /// Doubles the numbers it's given.
+/// #[derive(Default)]
+/// struct Doubler {
+///     input: Vec<u32>,
+///     output: String,
+/// }
+///
+/// impl IoHandler for Doubler {
+///     fn read(&mut self) -> io::Result<Input> {
+///         Ok(self.input.pop().map_or(Input::End, |n| Input::Number(n.to_string())))
+///     }
+///
+///     fn print_char(&mut self, c: char) -> io::Result<()> {
+///         self.output.push(c);
+///         Ok(())
+///     }
+///
+///     fn print_number(&mut self, number: &str) -> io::Result<()> {
+///         self.output.push_str(number);
+///         Ok(())
+///     }
+/// }
+///
+/// let mut handler = Doubler { input: vec![4, 21], ..Doubler::default() };
+///
+/// Interpreter::<SimpleProgramState>::new("_:[2*#A$_:]@", None, None)
+///     .with_io_handler(&mut handler)
+///     .run()
+///     .expect("The program failed");
+///
+/// assert_eq!(handler.output, "42\n8\n");
+/// ```
+pub trait IoHandler {
+	/// Read the input for `_`.
+	fn read(&mut self) -> io::Result<Input>;
+
+	/// Print a character for `$`.
+	fn print_char(&mut self, c: char) -> io::Result<()>;
+
+	/// Print a value as a decimal number for `#`.
+	fn print_number(&mut self, number: &str) -> io::Result<()>;
+
+	/// Print the characters of the whole stack, from top to bottom, for `p`.
+	fn print_string(&mut self, s: &str) -> io::Result<()> {
+		s.chars().try_for_each(|c| self.print_char(c))
+	}
+
+	/// Write any output which is still buffered.
+	///
+	/// This is called when the program terminates, fails or stops, before `_` reads, and when
+	/// [`run_for`](struct.Interpreter.html#method.run_for) or
+	/// [`run_until`](struct.Interpreter.html#method.run_until) return.
+	fn flush(&mut self) -> io::Result<()> {
+		Ok(())
+	}
+
+	/// Whether there is any input. If not, `_` does nothing.
+	fn has_input(&self) -> bool {
+		true
+	}
+
+	/// Whether printed values go anywhere. If not, `$`, `#` and `p` do nothing, and leave the
+	/// stack as it is.
+	fn has_output(&self) -> bool {
+		true
+	}
+}
+
+impl<H: IoHandler + ?Sized> IoHandler for &mut H {
+	fn read(&mut self) -> io::Result<Input> {
+		(**self).read()
+	}
+
+	fn print_char(&mut self, c: char) -> io::Result<()> {
+		(**self).print_char(c)
+	}
+
+	fn print_number(&mut self, number: &str) -> io::Result<()> {
+		(**self).print_number(number)
+	}
+
+	fn print_string(&mut self, s: &str) -> io::Result<()> {
+		(**self).print_string(s)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		(**self).flush()
+	}
+
+	fn has_input(&self) -> bool {
+		(**self).has_input()
+	}
+
+	fn has_output(&self) -> bool {
+		(**self).has_output()
+	}
+}
+
+impl<H: IoHandler + ?Sized> IoHandler for Box<H> {
+	fn read(&mut self) -> io::Result<Input> {
+		(**self).read()
+	}
+
+	fn print_char(&mut self, c: char) -> io::Result<()> {
+		(**self).print_char(c)
+	}
+
+	fn print_number(&mut self, number: &str) -> io::Result<()> {
+		(**self).print_number(number)
+	}
+
+	fn print_string(&mut self, s: &str) -> io::Result<()> {
+		(**self).print_string(s)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		(**self).flush()
+	}
+
+	fn has_input(&self) -> bool {
+		(**self).has_input()
+	}
+
+	fn has_output(&self) -> bool {
+		(**self).has_output()
+	}
+}
@@ -0,0 +1,72 @@
+//! Limits on the resources a program can use.
+
+use crate::error::Limit;
+use crate::program::{ProgramState, VecStack};
+use crate::value::Value;
+
+/// Limits on the resources a program can use, so untrusted programs can be run safely.
+///
+/// Nothing is limited by default. A program which goes over a limit is stopped with
+/// [`Error::LimitExceeded`](../error/enum.Error.html).
+///
+/// # Example
+///
+/// ```
+/// use col::interpreter::{Interpreter, Limits};
+/// use col::program::SimpleProgramState;
+///
+/// // never terminates
+/// let result = Interpreter::<SimpleProgramState>::new("1[]@", None, None)
+///     .with_limits(Limits { max_steps: Some(1000), ..Limits::default() })
+///     .run();
+///
+/// assert!(result.is_err());
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+	/// The most steps executed in total
+	pub max_steps: Option<u64>,
+	/// The most values held by a single stack
+	pub max_stack_depth: Option<usize>,
+	/// The most stacks in use at once, including the columns of the program
+	pub max_columns: Option<u32>,
+	/// The most values held by all of the stacks together
+	pub max_cells: Option<usize>,
+}
+
+impl Limits {
+	/// Check the stacks used by an instruction, given how many values there are in total.
+	pub(super) fn check_stacks<V: Value>(&self, local_stack: &VecStack<V>, remote_stack: Option<&VecStack<V>>, cells: usize) -> Result<(), Limit> {
+		if let Some(max) = self.max_stack_depth {
+			if local_stack.len().max(remote_stack.map_or(0, VecStack::len)) > max {
+				return Err(Limit::StackDepth(max));
+			}
+		}
+
+		match self.max_cells {
+			Some(max) if cells > max => Err(Limit::Cells(max)),
+			_ => Ok(()),
+		}
+	}
+
+	/// Create the stack of a remote column, collecting garbage first if there are too many stacks.
+	pub(super) fn init_stack<P: ProgramState>(&self, state: &mut P, program_len: u32, column: u32) -> Result<(), Limit> {
+		if let Some(max) = self.max_columns {
+			if state.nth(column).is_none() && state.stack_count() >= max {
+				state.discard_unused(&program_len, &column);
+
+				if state.stack_count() >= max {
+					return Err(Limit::Columns(max));
+				}
+			}
+		}
+
+		state.init_stack(&column);
+		Ok(())
+	}
+}
+
+/// The number of values held by the local and remote stacks.
+pub(super) fn stack_cells<V: Value>(local_stack: &VecStack<V>, remote_stack: Option<&VecStack<V>>) -> usize {
+	local_stack.len() + remote_stack.map_or(0, VecStack::len)
+}
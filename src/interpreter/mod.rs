@@ -9,11 +9,11 @@
 //! use col::program::SimpleProgramState;
 //!
 //! let mut stdout = stdout();
-//!	let mut stdin = stdin();
+//! let mut stdin = stdin();
 //!
-//!	Interpreter::<SimpleProgramState>::new("\"Hello world\"Arp@", Some(&mut stdin), Some(&mut stdout))
-//!		.run()
-//!		.expect("An I/O error occurred");
+//! Interpreter::<SimpleProgramState>::new("\"Hello world\"Arp@", Some(&mut stdin), Some(&mut stdout))
+//!     .run()
+//!     .expect("The program failed");
 //! ```
 //!
 //! It is also possible to register a callback after each step.
@@ -23,40 +23,241 @@
 //! use col::program::SimpleProgramState;
 //!
 //! Interpreter::<SimpleProgramState>::new("12345@", None, None)
-//! 	.with_step_callback(&|stacks| {
-//! 		for (index, stack) in stacks {
-//! 			println!("{}: {:?}", index, stack);
-//! 		}
-//! 	})
-//! 	.run_with_delay(20) // wait 20 ms between steps
-//! 	.expect("An I/O error occurred")
+//!     .with_step_callback(&|stacks| {
+//!         for (index, stack) in stacks {
+//!             println!("{}: {:?}", index, stack);
+//!         }
+//!     })
+//!     .run_with_delay(20) // wait 20 ms between steps
+//!     .expect("The program failed")
+//! ```
+//!
+//! A parsed [`Program`](../parser/struct.Program.html) can be run any number of times without
+//! parsing it again.
+//!
+//! ```
+//! use col::interpreter::Interpreter;
+//! use col::parser;
+//! use col::program::SimpleProgramState;
+//!
+//! let program = parser::parse("_:#$@");
+//!
+//! for input in &["a", "b", "c"] {
+//!     let mut output = Vec::new();
+//!
+//!     Interpreter::<SimpleProgramState>::from_program(&program, Some(&mut input.as_bytes()), Some(&mut output))
+//!         .run()
+//!         .expect("The program failed");
+//! }
+//! ```
+//!
+//! Programs which run for a long time can use the [bytecode backend](enum.Backend.html), which
+//! fuses common sequences of instructions together.
+//!
+//! ```
+//! use col::interpreter::{Backend, Interpreter};
+//! use col::program::SimpleProgramState;
+//!
+//! let mut output = Vec::new();
+//!
+//! Interpreter::<SimpleProgramState>::new("8[1-:#A$]@", None, Some(&mut output))
+//!     .with_backend(Backend::Bytecode)
+//!     .run()
+//!     .expect("The program failed");
 //! ```
 
+use std::borrow::Cow;
 use std::cell::{Ref, RefMut};
-use std::io::{Read, Write};
+use std::collections::VecDeque;
+use std::io::{self, Cursor, ErrorKind, Read, Write};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use rand::{RngCore, SeedableRng};
 
-use crate::parser::Instruction;
+use crate::error::{Error, Limit, Location, Trap};
+use crate::parser::{self, Column, Instruction, Program};
 use crate::program::{ProgramState, VecStack};
-use std::borrow::BorrowMut;
+use crate::value::Value;
+
+mod breakpoint;
+mod bytecode;
+mod cancel;
+mod charset;
+mod coverage;
+mod events;
+mod handler;
+mod limits;
+mod observer;
+mod profile;
+mod random;
+mod replay;
+mod snapshot;
+mod threading;
+
+use breakpoint::Debugging;
+use bytecode::Bytecode;
+use charset::Decoder;
+use events::PushedInput;
+use limits::stack_cells;
+use observer::{local_depth, remote_depth, Watched};
+use random::{Generator, Random};
+use replay::{fingerprint, ReplayRng};
+
+pub use breakpoint::{Breakpoint, BreakpointId, IntoCondition, StopReason, Watchpoint, WatchpointId};
+pub use cancel::CancellationHandle;
+pub use charset::{Charset, Unprintable};
+pub use coverage::Coverage;
+pub use events::OutputEvent;
+pub use handler::{Input, InputMode, IoHandler};
+pub use limits::Limits;
+pub use observer::Observer;
+pub use profile::Profiler;
+pub use random::DefaultRng;
+pub use replay::Recording;
+pub use snapshot::Snapshot;
+pub use threading::{Local, Sendable, Threading};
 
 /// How often automatic tne basic garbage collection will be called.
 const GC_STEPS: u32 = 8192;
 
+/// How many bytes of output are kept by default before they are written.
+const OUTPUT_BUFFER_SIZE: usize = 8192;
+
 /// Callback function for after each step.
-type StepCallback = dyn Fn(Vec<(u32, Ref<Vec<u32>>)>);
+type StepCallback<V> = dyn Fn(Vec<(u32, Ref<Vec<V>>)>);
+
+/// Where a program reads its input from, owned by the interpreter.
+type BoxedReader<'a, T> = Box<<T as Threading>::Reader<'a>>;
+
+/// Where a program writes its output to, owned by the interpreter.
+type BoxedWriter<'a, T> = Box<<T as Threading>::Writer<'a>>;
+
+/// A step callback the interpreter owns.
+type BoxedStepCallback<'a, T, V> = Box<<T as Threading>::StepCallback<'a, V>>;
+
+/// An observer the interpreter owns.
+type BoxedObserver<'a, T, V> = Box<<T as Threading>::Observer<'a, V>>;
+
+/// A custom input and output handler the interpreter owns.
+type BoxedIoHandler<'a, T> = Box<<T as Threading>::IoHandler<'a>>;
+
+/// The strategy used to execute a program.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+	/// Execute the parsed instructions directly, one at a time.
+	#[default]
+	Direct,
+	/// Compile each column to bytecode first, fusing common sequences of instructions into single
+	/// operations and keeping the current stacks borrowed across instructions.
+	///
+	/// Stepping with a delay or a step callback falls back to executing directly, since every
+	/// single step has to be observed.
+	Bytecode,
+}
+
+/// What happens when arithmetic overflows the value type.
+///
+/// Division by zero is not an overflow, and results in zero unless
+/// [`StrictMode::division_by_zero`](struct.StrictMode.html#structfield.division_by_zero) is set.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum OverflowPolicy {
+	/// Wrap around, e.g. `0 - 1` results in the maximum value.
+	#[default]
+	Wrap,
+	/// Stop at the minimum or maximum value, e.g. `0 - 1` results in zero.
+	Saturate,
+	/// Stop the program with a [`Trap::Overflow`](../error/enum.Trap.html) error.
+	Trap,
+}
+
+/// Which failed operations stop the program with a [`Trap`](../error/enum.Trap.html), instead of
+/// quietly using zero or doing nothing as the spec says.
+///
+/// Nothing is strict by default.
+///
+/// # Example
+///
+/// ```
+/// use col::interpreter::{Interpreter, StrictMode};
+/// use col::program::SimpleProgramState;
+///
+/// let result = Interpreter::<SimpleProgramState>::new("10/#@", None, None)
+///     .with_strict_mode(StrictMode { division_by_zero: true, ..StrictMode::default() })
+///     .run();
+///
+/// assert!(result.is_err());
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct StrictMode {
+	/// Popping a value from an empty local stack
+	pub empty_local_stack: bool,
+	/// Popping a value from an empty remote stack
+	pub empty_remote_stack: bool,
+	/// Reading a character after the end of the input
+	pub empty_input: bool,
+	/// Dividing by zero, or taking the remainder of it
+	pub division_by_zero: bool,
+	/// Moving values to or from the remote stack while it is the local stack
+	pub uninitialised_remote: bool,
+}
+
+impl StrictMode {
+	/// Stop the program at every failed operation.
+	pub fn all() -> Self {
+		StrictMode {
+			empty_local_stack: true,
+			empty_remote_stack: true,
+			empty_input: true,
+			division_by_zero: true,
+			uninitialised_remote: true,
+		}
+	}
+}
+
+/// How instructions behave when they can't be performed normally.
+#[derive(Copy, Clone, Debug, Default)]
+struct Rules {
+	overflow_policy: OverflowPolicy,
+	strict: StrictMode,
+}
 
 #[derive(Default)]
-pub struct Interpreter<'a, P: ProgramState> {
-	/// The source of the program
-	source: Vec<&'a str>,
-	/// Program input
-	reader: Option<&'a mut dyn Read>,
-	/// Program output
-	writer: Option<&'a mut dyn Write>,
+pub struct Interpreter<'a, P: ProgramState, T: Threading = Local> {
+	/// The compiled program
+	program: Cow<'a, Program>,
+	/// The program compiled for the bytecode backend, once needed
+	bytecode: Option<Bytecode>,
+	/// How the program is executed
+	backend: Backend,
+	/// What to do when arithmetic overflows or an operation fails
+	rules: Rules,
+	/// Limits on the resources the program can use
+	limits: Limits,
+	/// Stops the program when cancelled from outside
+	cancellation: Option<CancellationHandle>,
+	/// When the program is stopped if it is still running
+	deadline: Option<Instant>,
+	/// Where random values come from
+	random: Random<'a, T>,
+	/// Whether every run should behave the same, given the same input
+	deterministic: bool,
+	/// The program and options a replay was recorded with, until they've been checked
+	replayed: Option<(u64, String)>,
+	/// What `_` pushes at the end of the input
+	end_of_input: P::Value,
+	/// Program input and output
+	io: Io<'a, T>,
 	/// User-defined step callback
-	step_callback: Option<Box<&'a StepCallback>>,
+	step_callback: Option<BoxedStepCallback<'a, T, P::Value>>,
+	/// Sees every instruction, if set
+	observer: Option<BoxedObserver<'a, T, P::Value>>,
+	/// Where `_` found no input yet, so the observer isn't told about it again when it's retried
+	waiting_at: Option<Location>,
+	/// Where the program stops before it has to
+	debugging: Debugging<'a, P, T>,
+	/// Why the last step stopped the program, if it did
+	stopped: Option<StopReason>,
 	/// The memory stacks
 	state: P,
 	/// The index of the current local column
@@ -65,358 +266,1340 @@ pub struct Interpreter<'a, P: ProgramState> {
 	remote_column: u32,
 	/// Whether or not the interpreter is in string mode
 	is_string_mode: bool,
-	/// Instruction pointer
+	/// How many characters of the current string have been pushed
+	string_offset: u32,
+	/// Instruction pointer, as an index into the instructions of the current column
 	ip: u32,
+	/// How many steps have been executed
+	steps: u64,
+	/// How many values the stacks hold in total
+	cells: usize,
+	/// How many steps have been executed since the last garbage collection
+	gc_count: u32,
+	/// Whether or not the program has terminated
+	is_terminated: bool,
+}
+
+/// Program input and output
+#[derive(Default)]
+struct Io<'a, T: Threading> {
+	reader: Option<BoxedReader<'a, T>>,
+	writer: Option<BoxedWriter<'a, T>>,
+	/// Input recorded from an earlier run, which is read instead of the reader
+	replay: Option<Cursor<Vec<u8>>>,
+	/// Whether the input of the recorded run ended after what was recorded
+	is_replay_ended: bool,
+	/// Whether more input was read than was recorded
+	has_replay_run_out: bool,
+	/// Input pushed by the host, which is read instead of the reader
+	pushed: Option<PushedInput>,
+	/// Everything printed since the output was last taken, if collecting it instead of writing it
+	events: Option<Vec<OutputEvent>>,
+	/// How characters are encoded
+	charset: Charset,
+	/// What to do when a value isn't a character, if not what the instruction does by default
+	unprintable: Option<Unprintable>,
+	/// Input which has been read, but isn't a whole character yet
+	decoder: Decoder,
+	/// How `_` reads the input
+	input_mode: InputMode,
+	/// The line being read, in the line input mode
+	line: VecDeque<u8>,
+	/// Whether the input ended before the line did
+	is_line_ended: bool,
+	/// The digits of the number being read, in the number input mode
+	number: String,
+	/// Printed bytes which haven't been written yet
+	output: Vec<u8>,
+	/// How many bytes of output are kept before they are written
+	buffer_size: usize,
+	/// Handles everything in place of the above, if set
+	handler: Option<BoxedIoHandler<'a, T>>,
+	/// Every byte of input read so far, if recording
+	recorded: Option<Vec<u8>>,
+	/// Whether the input ended while recording
+	is_recorded_ended: bool,
+}
+
+/// How far a program got after executing some steps.
+#[derive(Debug)]
+pub enum ExecutionStatus {
+	/// The program can keep going.
+	Running,
+	/// The program reached `@`, and won't execute any more steps.
+	Terminated,
+	/// `_` found no input available yet, either because none has been
+	/// [pushed](struct.Interpreter.html#method.push_input) or because the reader returned
+	/// [`WouldBlock`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock).
+	/// Executing more steps resumes at the `_`, trying to read again.
+	NeedsInput,
+	/// The program was stopped by an error.
+	Error(Error),
+	/// A [breakpoint](struct.Breakpoint.html) or [watchpoint](enum.Watchpoint.html) stopped the
+	/// program. Executing more steps resumes it.
+	Stopped { reason: StopReason },
 }
 
 /// Result from an execution step
 struct StepResponse {
 	/// Is the program still alive after this step?
 	is_alive: bool,
-	/// Should the remote stack be initialized?
-	should_init_remote: bool,
+	/// Where the remote stack was set, if it should be initialized
+	init_remote: Option<Location>,
+	/// How many steps were actually executed
+	steps: u32,
+	/// Whether the step did nothing, because the column is empty
+	is_idle: bool,
 }
 
 impl Default for StepResponse {
 	fn default() -> Self {
-		StepResponse { is_alive: true, should_init_remote: false }
+		StepResponse { is_alive: true, init_remote: None, steps: 1, is_idle: false }
 	}
 }
 
 impl<'a, P: ProgramState> Interpreter<'a, P> {
-	/// Create a new col interpreter from a program
-	pub fn new(program: &'a str, reader: Option<&'a mut dyn Read>, writer: Option<&'a mut dyn Write>) -> Self {
-		let mut interpeter = Self::default();
-
-		interpeter.load_source(program);
-
-		interpeter.reader = reader;
-		interpeter.writer = writer;
+	/// Create a new col interpreter from source code
+	pub fn new(source: &str, reader: Option<&'a mut dyn Read>, writer: Option<&'a mut dyn Write>) -> Self {
+		Self::with_program(Cow::Owned(parser::parse(source)), box_reader(reader), box_writer(writer))
+	}
 
-		interpeter
+	/// Create a new col interpreter from an already parsed program, which can be reused afterwards.
+	pub fn from_program(program: &'a Program, reader: Option<&'a mut dyn Read>, writer: Option<&'a mut dyn Write>) -> Self {
+		Self::with_program(Cow::Borrowed(program), box_reader(reader), box_writer(writer))
 	}
 
 	/// Set a callback function to be called after each program step.
 	///
 	/// See module documentation for example.
-	pub fn with_step_callback(mut self, callback: &'a StepCallback) -> Self {
+	pub fn with_step_callback(mut self, callback: &'a StepCallback<P::Value>) -> Self {
 		self.step_callback = Some(Box::new(callback));
 		self
 	}
 
+	/// Set the random number generator used by `?`.
+	///
+	/// By default, a [`DefaultRng`](struct.DefaultRng.html) seeded by the operating system is used.
+	pub fn with_rng<R: RngCore + 'a>(mut self, rng: R) -> Self {
+		self.random.generator = Generator::Custom(Box::new(rng) as Box<dyn RngCore>);
+		self
+	}
+
+	/// Report everything the program does to an observer.
+	///
+	/// See [`Observer`](trait.Observer.html) for an example.
+	pub fn with_observer<O: Observer<P::Value> + 'a>(mut self, observer: O) -> Self {
+		self.observer = Some(Box::new(observer) as Box<dyn Observer<P::Value>>);
+		self
+	}
+
+	/// Handle input and output with a custom handler, instead of the reader and writer.
+	///
+	/// See [`IoHandler`](trait.IoHandler.html) for an example.
+	pub fn with_io_handler<H: IoHandler + 'a>(mut self, handler: H) -> Self {
+		self.io.handler = Some(Box::new(handler) as Box<dyn IoHandler>);
+		self
+	}
+}
+
+impl<'a, P: ProgramState> Interpreter<'a, P, Sendable> {
+	/// Create a new col interpreter which owns its program, input and output.
+	///
+	/// Unlike one which borrows them, it can be `'static`, and so be stored anywhere. Its input and
+	/// output have to be `Send`, as does everything else it holds, so it can be moved to another
+	/// thread.
+	///
+	/// # Example
+	///
+	/// ```
+	/// use std::thread;
+	///
+	/// use col::interpreter::{Interpreter, Sendable};
+	/// use col::parser;
+	/// use col::program::SimpleProgramState;
+	///
+	/// let program = parser::parse("_:#$@");
+	///
+	/// let running = thread::spawn(move || {
+	///     Interpreter::<SimpleProgramState, Sendable>::from_owned(program, Some(Box::new("a".as_bytes())), Some(Box::new(std::io::sink())))
+	///         .run()
+	/// });
+	///
+	/// running.join().unwrap().expect("The program failed");
+	/// ```
+	pub fn from_owned(program: Program, reader: Option<Box<dyn Read + Send + 'a>>, writer: Option<Box<dyn Write + Send + 'a>>) -> Self {
+		Self::with_program(Cow::Owned(program), reader, writer)
+	}
+
+	/// Set a callback function to be called after each program step, which the interpreter owns.
+	pub fn with_boxed_step_callback(mut self, callback: BoxedStepCallback<'a, Sendable, P::Value>) -> Self {
+		self.step_callback = Some(callback);
+		self
+	}
+
+	/// Set the random number generator used by `?`, which has to be `Send`.
+	///
+	/// By default, a [`DefaultRng`](struct.DefaultRng.html) seeded by the operating system is used.
+	pub fn with_rng<R: RngCore + Send + 'a>(mut self, rng: R) -> Self {
+		self.random.generator = Generator::Custom(Box::new(rng) as Box<dyn RngCore + Send>);
+		self
+	}
+
+	/// Report everything the program does to an observer, which has to be `Send`.
+	///
+	/// See [`Observer`](trait.Observer.html) for an example.
+	pub fn with_observer<O: Observer<P::Value> + Send + 'a>(mut self, observer: O) -> Self {
+		self.observer = Some(Box::new(observer) as Box<dyn Observer<P::Value> + Send>);
+		self
+	}
+
+	/// Handle input and output with a custom handler, instead of the reader and writer, which has
+	/// to be `Send`.
+	///
+	/// See [`IoHandler`](trait.IoHandler.html) for an example.
+	pub fn with_io_handler<H: IoHandler + Send + 'a>(mut self, handler: H) -> Self {
+		self.io.handler = Some(Box::new(handler) as Box<dyn IoHandler + Send>);
+		self
+	}
+}
+
+impl<'a, P: ProgramState, T: Threading> Interpreter<'a, P, T> {
+	fn with_program(program: Cow<'a, Program>, reader: Option<BoxedReader<'a, T>>, writer: Option<BoxedWriter<'a, T>>) -> Self {
+		let state = P::new(program.len());
+
+		Interpreter {
+			program,
+			io: Io { reader, writer, buffer_size: OUTPUT_BUFFER_SIZE, ..Io::default() },
+			state,
+			..Self::default()
+		}
+	}
+
+	/// Set the backend used to execute the program.
+	pub fn with_backend(mut self, backend: Backend) -> Self {
+		self.backend = backend;
+		self
+	}
+
+	/// Set what happens when arithmetic overflows. Wraps by default.
+	pub fn with_overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+		self.rules.overflow_policy = policy;
+		self
+	}
+
+	/// Set which failed operations stop the program. Nothing is strict by default.
+	pub fn with_strict_mode(mut self, strict: StrictMode) -> Self {
+		self.rules.strict = strict;
+		self
+	}
+
+	/// Set limits on the resources the program can use. Nothing is limited by default.
+	pub fn with_limits(mut self, limits: Limits) -> Self {
+		self.limits = limits;
+		self
+	}
+
+	/// Stop the program when the handle, or any clone of it, is cancelled.
+	///
+	/// See [`CancellationHandle`](struct.CancellationHandle.html) for an example.
+	pub fn with_cancellation(mut self, handle: CancellationHandle) -> Self {
+		self.cancellation = Some(handle);
+		self
+	}
+
+	/// Stop the program if it's still running at the deadline.
+	///
+	/// The deadline is kept even if the interpreter is [deterministic](#method.with_deterministic),
+	/// so a program which hangs is still stopped.
+	pub fn with_deadline(mut self, deadline: Instant) -> Self {
+		self.deadline = Some(deadline);
+		self
+	}
+
+	/// Seed the default random number generator, so `?` pushes the same values every run.
+	pub fn with_seed(mut self, seed: u64) -> Self {
+		self.random.generator = Generator::Default(DefaultRng::seed_from_u64(seed));
+		self
+	}
+
+	/// Make every run of the program behave the same, given the same input.
+	///
+	/// Unless another random number generator or seed is set, the default one is seeded with
+	/// zero. Reaching a [deadline](#method.with_deadline) is the one thing which can still differ
+	/// between runs, since it depends on how fast the machine is, so use
+	/// [`Limits::max_steps`](struct.Limits.html#structfield.max_steps) as well for a limit which
+	/// doesn't.
+	///
+	/// # Example
+	///
+	/// ```
+	/// use col::interpreter::Interpreter;
+	/// use col::program::SimpleProgramState;
+	///
+	/// let run = || {
+	///     let mut output = Vec::new();
+	///
+	///     Interpreter::<SimpleProgramState>::new("???#######@", None, Some(&mut output))
+	///         .with_deterministic(true)
+	///         .run()
+	///         .expect("The program failed");
+	///
+	///     output
+	/// };
+	///
+	/// assert_eq!(run(), run());
+	/// ```
+	pub fn with_deterministic(mut self, deterministic: bool) -> Self {
+		self.deterministic = deterministic;
+		self
+	}
+
+	/// Record everything the program reads from outside, which can be retrieved with
+	/// [`recording`](#method.recording).
+	///
+	/// Nothing is recorded with a custom [`IoHandler`](trait.IoHandler.html), since it reads the
+	/// input itself.
+	///
+	/// See [`Recording`](struct.Recording.html) for an example.
+	pub fn with_recording(mut self, record: bool) -> Self {
+		self.io.recorded = record.then(Vec::new);
+		self.random.recorded = record.then(Vec::new);
+		self
+	}
+
+	/// Repeat a recorded run, reading input and random values from the recording instead.
+	///
+	/// Running fails with [`Error::InvalidReplay`](../error/enum.Error.html) if the recording is of
+	/// another program, or was made with another cell type, [charset](#method.with_charset) or
+	/// [input mode](#method.with_input_mode). It also fails if a random number generator or seed
+	/// is set after the replay, or a custom [`IoHandler`](trait.IoHandler.html) is set at all,
+	/// since either would take the place of the recording. Reading more than was recorded fails
+	/// with [`Error::ReplayEnded`](../error/enum.Error.html).
+	pub fn with_replay(mut self, recording: Recording) -> Self {
+		self.replayed = Some((recording.program, recording.options));
+		self.io.replay = Some(Cursor::new(recording.input));
+		self.io.is_replay_ended = recording.is_input_ended;
+		self.random.generator = Generator::Replay(ReplayRng::new(recording.random));
+		self
+	}
+
+	/// Set how the characters of `_`, `$` and `p` are encoded. UTF-8 by default.
+	pub fn with_charset(mut self, charset: Charset) -> Self {
+		self.io.charset = charset;
+		self
+	}
+
+	/// Set what happens when a value which isn't a character is printed. By default, `$` stops
+	/// the program with an error, and `p` skips the value.
+	pub fn with_unprintable(mut self, unprintable: Unprintable) -> Self {
+		self.io.unprintable = Some(unprintable);
+		self
+	}
+
+	/// Set how many bytes of output are kept before they are written, 8 KiB by default.
+	///
+	/// Output is also written when the program terminates, fails or stops, before `_` reads, and
+	/// when [`run_for`](#method.run_for) or [`run_until`](#method.run_until) return. A single
+	/// [`step`](#method.step) which leaves the program running keeps it buffered, until
+	/// [`flush`](#method.flush) is called. With a size of zero, everything is written and flushed as
+	/// soon as it is printed.
+	pub fn with_output_buffer(mut self, size: usize) -> Self {
+		self.io.buffer_size = size;
+		self
+	}
+
+	/// Set how `_` reads the input of the reader. Reads a character at a time by default.
+	pub fn with_input_mode(mut self, mode: InputMode) -> Self {
+		self.io.input_mode = mode;
+		self
+	}
+
+	/// Set the value `_` pushes at the end of the input, instead of zero. A value which can't be a
+	/// character, like `u32::MAX`, tells the end apart from a NUL character.
+	pub fn with_end_of_input(mut self, value: P::Value) -> Self {
+		self.end_of_input = value;
+		self
+	}
+
+	/// Take input from bytes pushed with [`push_input`](#method.push_input) instead of the reader.
+	///
+	/// When `_` finds nothing pushed yet, stepping stops with
+	/// [`ExecutionStatus::NeedsInput`](enum.ExecutionStatus.html) and resumes at the `_` once more
+	/// has been pushed, or the input has been [closed](#method.close_input). `run` fails with a
+	/// [`WouldBlock`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock) error
+	/// instead.
+	///
+	/// See [`OutputEvent`](enum.OutputEvent.html) for an example.
+	pub fn with_suspending_input(mut self) -> Self {
+		self.io.pushed.get_or_insert_with(PushedInput::default);
+		self
+	}
+
+	/// Collect everything the program prints as events, taken with [`take_output`](#method.take_output),
+	/// instead of writing it to the writer.
+	pub fn with_output_events(mut self) -> Self {
+		self.io.events.get_or_insert_with(Vec::new);
+		self
+	}
+
+	/// Add bytes to the end of the input, switching to [suspending input](#method.with_suspending_input)
+	/// if it isn't used yet.
+	pub fn push_input(&mut self, bytes: &[u8]) {
+		self.io.pushed.get_or_insert_with(PushedInput::default).push(bytes);
+	}
+
+	/// Signal that no more input will be pushed, so `_` reads the end of the input once everything
+	/// pushed so far has been read.
+	pub fn close_input(&mut self) {
+		self.io.pushed.get_or_insert_with(PushedInput::default).close();
+	}
+
+	/// Take everything printed since the output was last taken, if [collecting it](#method.with_output_events).
+	pub fn take_output(&mut self) -> Vec<OutputEvent> {
+		self.io.events.as_mut().map(std::mem::take).unwrap_or_default()
+	}
+
+	/// Everything the program has read from outside so far, if it is being recorded.
+	///
+	/// There is no recording with a custom [`IoHandler`](trait.IoHandler.html).
+	pub fn recording(&self) -> Option<Recording> {
+		if self.io.handler.is_some() {
+			return None;
+		}
+
+		Some(Recording {
+			program: fingerprint(&self.program),
+			options: self.replay_options(),
+			input: self.io.recorded.clone()?,
+			is_input_ended: self.io.is_recorded_ended,
+			random: self.random.recorded.clone()?,
+		})
+	}
+
 	/// Executes the program until terminates.
 	/// This function is blocking and will return when the program has completed execution.
-	pub fn run(&mut self) -> std::io::Result<()> {
+	///
+	/// It doesn't stop at [breakpoints](#method.add_breakpoint) or watchpoints.
+	pub fn run(&mut self) -> Result<(), Error> {
 		self.run_with_delay(0)
 	}
 
 	/// Executes the program until it terminates with a specified delay between each step.
 	/// This function is blocking and will return when the program has completed execution
-	pub fn run_with_delay(&mut self, delay_ms: u64) -> std::io::Result<()> {
-		let mut gc_count = 0;
+	pub fn run_with_delay(&mut self, delay_ms: u64) -> Result<(), Error> {
+		// every step has to be seen when there is a delay or a callback
+		let use_bytecode = self.uses_bytecode() && delay_ms == 0;
 
 		// keep stepping until terminated
-		// first group is condition, second is body for delay
-		while {
-			// do execution step
-			let result = self.step()?;
-
-			// do garbage collection
-			if gc_count % GC_STEPS == 0 {
-				self.state.discard_unused(&self.program_len(), &self.remote_column);
-			}
-			gc_count += 1;
-
-			// ensure the remote stack is initialized
-			if result.should_init_remote {
-				self.state.init_stack(&self.remote_column);
-			}
-
-			if let Some(callback) = &self.step_callback {
-				&callback(self.state.stacks());
+		while !self.is_terminated {
+			if let Err(error) = self.advance(GC_STEPS, use_bytecode) {
+				// what was printed before the error is still written, though the error matters more
+				let _ = self.flush();
+				return Err(error);
 			}
 
-			result.is_alive
-		} {
 			// we don't even want to call the thread sleep if 0, because
 			// it might still pause the thread for a bit (citation needed)
-			if delay_ms != 0 {
+			if delay_ms != 0 && !self.is_terminated {
+				self.flush()?;
 				thread::sleep(Duration::from_millis(delay_ms));
 			}
 		}
 
-		Ok(())
+		self.flush()
 	}
 
-	fn load_source(&mut self, program: &'a str) {
-		self.source = program.lines().collect();
-		self.state = P::new(self.program_len());
+	/// Write any output which is still buffered.
+	pub fn flush(&mut self) -> Result<(), Error> {
+		Ok(self.io.handler().flush()?)
 	}
 
-	fn current_line(&self) -> &'a str {
-		self.source.get(self.local_column as usize).unwrap_or(&"")
+	/// Execute a single step of the program.
+	///
+	/// Buffered output is only written if the program can't keep going.
+	pub fn step(&mut self) -> ExecutionStatus {
+		match self.execute_steps(1, false) {
+			ExecutionStatus::Running => ExecutionStatus::Running,
+			status => self.flushed(status),
+		}
 	}
 
-	/// Number of program-defined columns
-	fn program_len(&self) -> u32 {
-		self.source.len() as u32
+	/// Execute up to `steps` steps of the program, stopping early if it can't keep going.
+	pub fn run_for(&mut self, steps: u64) -> ExecutionStatus {
+		let status = self.execute_steps(steps, self.uses_bytecode());
+		self.flushed(status)
 	}
 
-	/// Find the matching right bracket forwards
-	fn matching_forwards(&self) -> u32 {
-		let iter = (self.ip + 1)..self.current_line().len() as u32;
-		self.matching(&Instruction::LeftBracket, &Instruction::RightBracket, iter)
+	/// Execute steps until the predicate, which is checked after every step, is true.
+	///
+	/// # Example
+	///
+	/// ```
+	/// use col::interpreter::{ExecutionStatus, Interpreter};
+	/// use col::program::SimpleProgramState;
+	///
+	/// let mut interpreter = Interpreter::<SimpleProgramState>::new("1\\n;\n@", None, None);
+	/// let status = interpreter.run_until(|interpreter| interpreter.local_column() == 1);
+	///
+	/// assert!(matches!(status, ExecutionStatus::Running));
+	/// assert_eq!(interpreter.ip(), 0);
+	/// ```
+	pub fn run_until<F: FnMut(&Self) -> bool>(&mut self, mut predicate: F) -> ExecutionStatus {
+		loop {
+			match self.execute_steps(1, false) {
+				ExecutionStatus::Running if !predicate(self) => continue,
+				status => return self.flushed(status),
+			}
+		}
 	}
 
-	/// Find the matching left bracket backwards.
-	fn matching_backwards(&self) -> u32 {
-		self.matching(&Instruction::RightBracket, &Instruction::LeftBracket, (0..self.ip - 1).rev())
+	/// The index of the column being executed.
+	pub fn local_column(&self) -> u32 {
+		self.local_column
 	}
 
-	/// Used by `matching_backwards` and `matching_forwards`.
-	fn matching<I>(&self, current: &Instruction, matching: &Instruction, iter: I) -> u32
-		where I: Iterator<Item = u32> {
-		let line = self.current_line();
+	/// The index of the column whose stack is the remote stack.
+	pub fn remote_column(&self) -> u32 {
+		self.remote_column
+	}
 
-		// depth of other bracket pairs, 0 is top-level
-		let mut depth = 0;
+	/// The instruction pointer, as an index into the [instructions](../parser/struct.Column.html#method.instructions)
+	/// of the local column.
+	///
+	/// After an error, it points at the instruction which failed. While in string mode, it points
+	/// at the `"` which started the string.
+	pub fn ip(&self) -> u32 {
+		self.ip
+	}
 
-		for i in iter {
-			let instr = line.chars().nth(i as usize)
-				.and_then(|c| Instruction::from_char(&c));
+	/// Whether or not the interpreter is pushing the characters of a string.
+	pub fn is_string_mode(&self) -> bool {
+		self.is_string_mode
+	}
 
-			if instr == Some(*current) {
-				depth += 1;
-			} else if instr == Some(*matching) {
-				if depth == 0 {
-					return i;
-				} else {
-					depth -= 1;
-				}
+	/// How many steps have been executed.
+	pub fn steps(&self) -> u64 {
+		self.steps
+	}
+
+	/// The stacks of the program.
+	pub fn state(&self) -> &P {
+		&self.state
+	}
+
+	/// Replace the values of a stack, the top value last, creating the stack if it doesn't exist.
+	///
+	/// Changing the stacks through [`state`](#method.state) instead would leave the
+	/// [limits](struct.Limits.html) counting the wrong number of values.
+	pub fn set_stack(&mut self, index: u32, values: Vec<P::Value>) {
+		self.state.init_stack(&index);
+
+		let mut stack = self.state.nth(index).unwrap().borrow_mut();
+		self.cells = (self.cells + values.len()).saturating_sub(stack.len());
+
+		stack.clear();
+
+		for value in values {
+			stack.push(value);
+		}
+	}
+
+	/// Stop the program before an instruction, returning an identifier to remove the breakpoint.
+	///
+	/// Only stepping through the program stops it, with [`run_for`](#method.run_for) for example,
+	/// which returns [`Stopped`](enum.ExecutionStatus.html#variant.Stopped). Executing more steps
+	/// from there executes the instruction without stopping at it again. [`run`](#method.run)
+	/// goes past every breakpoint and watchpoint.
+	///
+	/// With any breakpoints or watchpoints, the [bytecode backend](enum.Backend.html) isn't used.
+	pub fn add_breakpoint(&mut self, breakpoint: Breakpoint<'a, P, T>) -> BreakpointId {
+		self.debugging.add_breakpoint(breakpoint)
+	}
+
+	/// Remove a breakpoint, returning whether there was one.
+	pub fn remove_breakpoint(&mut self, id: BreakpointId) -> bool {
+		self.debugging.remove_breakpoint(id)
+	}
+
+	/// Stop the program after an instruction changes a stack, returning an identifier to remove the
+	/// watchpoint.
+	///
+	/// Programs stop at watchpoints the same way as at
+	/// [breakpoints](#method.add_breakpoint).
+	pub fn add_watchpoint(&mut self, watchpoint: Watchpoint) -> WatchpointId {
+		self.debugging.add_watchpoint(watchpoint)
+	}
+
+	/// Remove a watchpoint, returning whether there was one.
+	pub fn remove_watchpoint(&mut self, id: WatchpointId) -> bool {
+		self.debugging.remove_watchpoint(id)
+	}
+
+	/// The options which change how a recorded run is replayed.
+	fn replay_options(&self) -> String {
+		format!("{} {:?} {:?}", P::Value::NAME, self.io.charset, self.io.input_mode)
+	}
+
+	/// Check a replay was recorded from this program, with the same options.
+	fn check_replay(&self, program: u64, options: &str) -> Result<(), Error> {
+		if program != fingerprint(&self.program) {
+			return Err(Error::InvalidReplay("it was recorded from another program"));
+		}
+
+		if options != self.replay_options() {
+			return Err(Error::InvalidReplay("it was recorded with another cell type, charset or input mode"));
+		}
+
+		if !matches!(self.random.generator, Generator::Replay(_)) {
+			return Err(Error::InvalidReplay("another random number generator was set after it"));
+		}
+
+		if self.io.handler.is_some() {
+			return Err(Error::InvalidReplay("a custom I/O handler would take the place of its input"));
+		}
+
+		Ok(())
+	}
+
+	/// Whether the bytecode backend should be used when the steps don't have to be seen.
+	fn uses_bytecode(&self) -> bool {
+		self.backend == Backend::Bytecode && self.step_callback.is_none() && self.observer.is_none() && !self.debugging.is_active()
+	}
+
+	fn execute_steps(&mut self, steps: u64, use_bytecode: bool) -> ExecutionStatus {
+		let mut remaining = steps;
+		let mut status = ExecutionStatus::Running;
+
+		while remaining > 0 && !self.is_terminated {
+			match self.advance(remaining.min(GC_STEPS as u64) as u32, use_bytecode) {
+				Ok(steps) => remaining -= steps as u64,
+				// the reader has nothing yet, but may later
+				Err(Error::Io(error)) if error.kind() == ErrorKind::WouldBlock => {
+					status = ExecutionStatus::NeedsInput;
+					break;
+				},
+				Err(error) => {
+					status = ExecutionStatus::Error(error);
+					break;
+				},
+			}
+
+			if let Some(reason) = self.stopped.take() {
+				status = ExecutionStatus::Stopped { reason };
+				break;
+			}
+		}
+
+		// a program stopped by its last instruction still has to be told it terminated
+		if self.is_terminated && matches!(status, ExecutionStatus::Running) {
+			status = ExecutionStatus::Terminated;
+		}
+
+		status
+	}
+
+	/// Write the buffered output once control goes back to the host, which should see everything
+	/// printed so far.
+	fn flushed(&mut self, status: ExecutionStatus) -> ExecutionStatus {
+		match self.flush() {
+			Err(error) if !matches!(status, ExecutionStatus::Error(_)) => ExecutionStatus::Error(error),
+			_ => status,
+		}
+	}
+
+	/// Execute at least one and up to `budget` steps, along with everything which has to be done
+	/// in between, returning how many steps were executed.
+	fn advance(&mut self, budget: u32, use_bytecode: bool) -> Result<u32, Error> {
+		if self.program.is_empty() {
+			return Err(Error::EmptyProgram);
+		}
+
+		if let Some(max) = self.limits.max_columns {
+			if self.program_len() > max {
+				return Err(Limit::Columns(max).at(self.location()));
 			}
 		}
 
-		return 0;
+		self.check_interrupted()?;
+
+		if let Some((program, options)) = &self.replayed {
+			self.check_replay(*program, options)?;
+			self.replayed = None;
+		}
+
+		let mut budget = budget.min(GC_STEPS - self.gc_count);
+
+		if let Some(max) = self.limits.max_steps {
+			if self.steps >= max {
+				return Err(Limit::Steps(max).at(self.location()));
+			}
+
+			budget = budget.min((max - self.steps).min(u32::MAX as u64) as u32);
+		}
+
+		// a stop from an earlier step which failed afterwards is stale
+		self.stopped = None;
+
+		if let Some(reason) = self.check_breakpoints() {
+			self.stopped = Some(reason);
+			return Ok(0);
+		}
+
+		// do execution step, falling back to a direct step if the bytecode can't make progress
+		let mut result = StepResponse { steps: 0, ..StepResponse::default() };
+
+		if use_bytecode {
+			result = self.run_bytecode(budget)?;
+		}
+
+		if result.steps == 0 {
+			result = self.step_direct()?;
+		}
+
+		self.steps += result.steps as u64;
+		self.is_terminated = !result.is_alive;
+
+		// do garbage collection
+		self.gc_count += result.steps;
+		if self.gc_count >= GC_STEPS {
+			self.state.discard_unused(&self.program_len(), &self.remote_column);
+			self.gc_count = 0;
+		}
+
+		// ensure the remote stack is initialized
+		if let Some(location) = result.init_remote {
+			self.limits.init_stack(&mut self.state, self.program.len(), self.remote_column)
+				.map_err(|limit| limit.at(location))?;
+		}
+
+		if let Some(observer) = self.observer.as_deref_mut().filter(|_| !result.is_idle) {
+			let (local_column, remote_column) = (self.local_column, self.remote_column);
+			let local = self.state.nth(local_column).unwrap().borrow();
+			let remote = self.state.nth(remote_column)
+				.filter(|_| local_column != remote_column)
+				.map(|stack| stack.borrow());
+
+			observer.after_instruction(local.values(), remote.as_ref().map(|stack| stack.values().as_slice()));
+		}
+
+		if let Some(callback) = &self.step_callback {
+			callback(self.state.stacks());
+		}
+
+		Ok(result.steps)
 	}
 
-	/// Safely increment the instruction pointer by one
-	fn increment_ip(&mut self) {
-		self.ip += 1;
-		if self.current_line().len() > 0 {
-			self.ip = self.ip % self.current_line().len() as u32;
+	/// The breakpoint to stop at before executing the next instruction, if any.
+	fn check_breakpoints(&mut self) -> Option<StopReason> {
+		// the characters of a string aren't instructions
+		if self.is_string_mode || !self.debugging.has_breakpoints() {
+			return None;
 		}
+
+		let location = self.location();
+		let instruction = self.program.columns()[self.local_column as usize].instruction(self.ip)?;
+		let local = self.state.nth(self.local_column).unwrap().borrow();
+
+		self.debugging.breakpoint(location, instruction, local.values(), &self.state, self.steps)
+	}
+
+	fn current_column(&self) -> &Column {
+		// the local column is always kept within the program
+		&self.program.columns()[self.local_column as usize]
+	}
+
+	/// Check whether the program has been cancelled or run out of time.
+	fn check_interrupted(&self) -> Result<(), Error> {
+		if self.cancellation.as_ref().is_some_and(CancellationHandle::is_cancelled) {
+			return Err(Error::Cancelled { steps: self.steps, location: self.location() });
+		}
+
+		if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+			return Err(Error::DeadlineExceeded { steps: self.steps, location: self.location() });
+		}
+
+		Ok(())
+	}
+
+	/// Where the next instruction will be executed
+	fn location(&self) -> Location {
+		Location { column: self.local_column, ip: self.ip }
+	}
+
+	/// Number of program-defined columns
+	fn program_len(&self) -> u32 {
+		self.program.len()
 	}
 
 	/// Perform one program step
-	fn step(&mut self) -> std::io::Result<StepResponse> {
+	fn step_direct(&mut self) -> Result<StepResponse, Error> {
 		let mut step_result = StepResponse::default();
+		self.debugging.is_entering = false;
+
+		// only borrow the program, so the rest of the interpreter can still be modified
+		let column = &self.program.columns()[self.local_column as usize];
 
-		let line = self.current_line();
+		if column.is_empty() {
+			// nothing to execute, so the program is stuck here
+			step_result.is_idle = true;
+			return Ok(step_result);
+		}
 
 		// string mode stuff
 		if self.is_string_mode {
-			let c = line.chars().nth(self.ip as usize);
-			let instr = c.and_then(|c| Instruction::from_char(&c));
+			let string = column.string(self.ip);
 
-			// prioritize exiting string mode
-			if instr == Some(Instruction::StringMode) {
+			let location = Location { column: self.local_column, ip: self.ip };
+
+			// push a raw value to the stack
+			let value = P::Value::from_char(string[self.string_offset as usize]);
+			self.string_offset += 1;
+
+			if let Some(observer) = &mut self.observer {
+				observer.before_instruction(self.local_column, location.ip, Instruction::StringMode);
+				observer.on_push(self.local_column, &value);
+			}
+
+			// leave string mode after the last character
+			if self.string_offset as usize == string.len() {
 				self.is_string_mode = false;
-			} else if let Some(c) = c {
-				// push a raw value to the stack
-				self.state.nth(self.local_column).unwrap().borrow_mut().push(c as u32);
+				self.ip = column.jump(self.ip);
 			}
 
-			self.increment_ip();
-		} else {
-			let mut instr = None;
+			let mut local_stack = self.state.nth(self.local_column).unwrap().borrow_mut();
+			let watched = self.debugging.is_watching().then(|| Watched::new(self.local_column, &local_stack, Some(0)));
+
+			local_stack.push(value);
+			self.cells += 1;
 
-			// TODO trim out invalid characters?
+			self.limits.check_stacks(&local_stack, None, self.cells).map_err(|limit| limit.at(location))?;
 
-			// find the next valid instruction
-			while instr == None {
-				instr = line.chars().nth(self.ip as usize).and_then(|c| Instruction::from_char(&c));
-				self.increment_ip();
+			if let Some(watched) = watched {
+				self.stopped = self.debugging.watchpoint(location, Instruction::StringMode, &[watched.change(&local_stack)]);
 			}
+		} else {
+			let ip = self.ip;
+			let instr = column.instructions()[ip as usize];
+			let is_retry = self.waiting_at.take() == Some(self.location());
 
-			// execute and pass on result
-			self.execute_instruction(instr.unwrap(), &mut step_result)?;
+			if let Some(observer) = self.observer.as_mut().filter(|_| !is_retry) {
+				observer.before_instruction(self.local_column, ip, instr);
+			}
+
+			self.ip = (ip + 1) % column.len();
+
+			// execute and pass on result, staying at the instruction if it failed
+			if let Err(error) = self.execute_instruction(ip, instr, &mut step_result) {
+				self.ip = ip;
+
+				if matches!(&error, Error::Io(error) if error.kind() == ErrorKind::WouldBlock) {
+					self.waiting_at = Some(self.location());
+				}
+
+				return Err(error);
+			}
 		};
 
-		return Ok(step_result);
+		Ok(step_result)
 	}
 
-	fn execute_instruction(&mut self, instruction: Instruction, step_result: &mut StepResponse) -> std::io::Result<()> {
+	fn execute_instruction(&mut self, ip: u32, instruction: Instruction, step_result: &mut StepResponse) -> Result<(), Error> {
+		let location = Location { column: self.local_column, ip };
+
 		// TODO this really doesn't need to be done each iteration
 		// *but* it does simplify execution flow, so we'll keep it here until it becomes a problem
-		let mut local_stack: RefMut<VecStack> = self.state.nth(self.local_column).unwrap().borrow_mut();
-		let mut remote_stack: Option<RefMut<VecStack>> = self.state.nth(self.remote_column)
+		// (the bytecode backend avoids it)
+		let mut local_stack: RefMut<VecStack<P::Value>> = self.state.nth(self.local_column).unwrap().borrow_mut();
+		let mut remote_stack: Option<RefMut<VecStack<P::Value>>> = self.state.nth(self.remote_column)
 			.filter(|_| self.local_column != self.remote_column) // avoid a BorrowMutError
-			.and_then(|v| {
-				Some(v.borrow_mut())
-			});
+			.map(|v| v.borrow_mut());
+
+		let cells_before = stack_cells(&local_stack, remote_stack.as_deref());
+
+		// what the instruction pops and pushes is only worked out when it's observed or watched
+		let (local_column, remote_column) = (self.local_column, self.remote_column);
+		let watched = (self.observer.is_some() || self.debugging.is_watching()).then(|| {
+			let remote = remote_stack.as_deref()
+				.and_then(|stack| remote_depth(instruction).map(|depth| Watched::new(remote_column, stack, depth)));
+
+			(Watched::new(local_column, &local_stack, local_depth(instruction)), remote)
+		});
 
 		match instruction {
-			Instruction::PushLeftIndex => {
-				let pos = self.local_column.wrapping_sub(1);
-				local_stack.push(pos);
-			},
-			Instruction::PushRightIndex => {
-				let pos = self.local_column.wrapping_add(1);
-				local_stack.push(pos);
-			},
-			Instruction::PushCurrentIndex => {
-				local_stack.push(self.local_column);
-			},
 			Instruction::SetLocalColumn => {
-				self.local_column = local_stack.pop() % self.program_len() as u32;
+				self.local_column = self.rules.pop_local(&mut local_stack).map_err(|trap| trap.at(location))?.to_u32() % self.program_len();
 				self.ip = 0; // we'll begin executing here
+				self.debugging.is_entering = true;
 			}
 			Instruction::SetRemoteStack => {
-				self.remote_column = local_stack.pop();
+				self.remote_column = self.rules.pop_local(&mut local_stack).map_err(|trap| trap.at(location))?.to_u32();
 
 				// this will ensure the stack is available the next iteration
-				if self.remote_column >= self.program_len() as u32 {
-					step_result.should_init_remote = true;
+				if self.remote_column >= self.program_len() {
+					step_result.init_remote = Some(location);
 				}
 			},
-			Instruction::MoveToRemote => {
-				if let Some(remote_stack) = &mut remote_stack { // redundant otherwise
-					remote_stack.push(local_stack.pop());
-				}
-			},
-			Instruction::MoveToLocal => {
-				if let Some(remote_stack) = &mut remote_stack { // redundant otherwise
-					local_stack.push(remote_stack.pop());
-				}
-			},
-			Instruction::SwapTop => {
-				let (a, b) = local_stack.pop2();
-				local_stack.push(a);
-				local_stack.push(b);
-			},
-			Instruction::DuplicateTop => {
-				let value = local_stack.peek();
-				local_stack.push(value);
-			},
-			Instruction::Discard => {
-				local_stack.pop();
-			},
-			Instruction::Clear => {
-				local_stack.clear();
-			},
-			Instruction::SwapStacks => {
-				if let Some(remote_stack) = &mut remote_stack {
-					// TODO could take advantage of RefCell swap
-					VecStack::swap(local_stack.borrow_mut(), remote_stack);
-				}
-			},
-			Instruction::Reverse => {
-				local_stack.reverse();
-			},
-			Instruction::Value(value) => {
-				local_stack.push(value);
-			},
 			Instruction::LeftBracket => {
-				if local_stack.peek() == 0 {
-					self.ip = self.matching_forwards();
+				if local_stack.peek().is_zero() {
+					self.ip = self.current_column().jump(ip);
 				}
 			},
 			Instruction::RightBracket => {
-				if local_stack.peek() != 0 {
-					self.ip = self.matching_backwards();
+				if !local_stack.peek().is_zero() {
+					self.ip = self.current_column().jump(ip);
 				}
 			},
-			Instruction::Add => {
-				let (a, b) = local_stack.pop2();
-				local_stack.push(b.wrapping_add(a));
-			},
-			Instruction::Subtract => {
-				let (a, b) = local_stack.pop2();
-				local_stack.push(b.wrapping_sub(a));
-			},
-			Instruction::Multiply => {
-				let (a, b) = local_stack.pop2();
-				local_stack.push(b.wrapping_mul(a));
-			},
-			Instruction::Divide => {
-				let (a, b) = local_stack.pop2();
-				local_stack.push(b.wrapping_div(a));
+			Instruction::StringMode => {
+				// string mode is always left from `step`, after the last character is pushed
+				if self.current_column().string(ip).is_empty() {
+					self.ip = self.current_column().jump(ip);
+				} else {
+					self.is_string_mode = true;
+					self.string_offset = 0;
+					self.ip = ip;
+				}
 			},
-			Instruction::Modulo => {
-				let (a, b) = local_stack.pop2();
-				local_stack.push(b.wrapping_rem(a)); // actually equivalent to b % a because unsigned
+			Instruction::Input | Instruction::PrintChar | Instruction::PrintNumber | Instruction::PrintAll => {
+				let observer = self.observer.as_mut().map(|observer| observer as &mut dyn Observer<P::Value>);
+				self.io.execute(instruction, &mut local_stack, self.rules, &self.end_of_input, location, observer)?;
 			},
-			Instruction::Equals => {
-				let (a, b) = local_stack.pop2();
-				local_stack.push((b == a) as u32);
+			Instruction::Terminate => {
+				step_result.is_alive = false;
 			},
-			Instruction::GreaterThan => {
-				let (a, b) = local_stack.pop2();
-				local_stack.push((b > a) as u32);
+			Instruction::Random => {
+				local_stack.push(self.random.next(self.deterministic).ok_or(Error::ReplayEnded { location })?);
 			},
-			Instruction::BitwiseNand => {
-				let (a, b) = local_stack.pop2();
-				local_stack.push(!(a & b));
+			_ => {
+				execute_stack_instruction(instruction, self.local_column, &mut local_stack, remote_stack.as_deref_mut(), self.rules)
+					.map_err(|trap| trap.at(location))?;
 			},
-			Instruction::LogicalAnd => {
-				let (a, b) = local_stack.pop2();
-				local_stack.push((a != 0 && b != 0) as u32);
+		};
+
+		if let (Some(observer), Some((local, remote))) = (self.observer.as_mut(), &watched) {
+			local.report(&local_stack, observer);
+
+			if let (Some(remote), Some(remote_stack)) = (remote, &remote_stack) {
+				remote.report(remote_stack, observer);
 			}
-			Instruction::LogicalOr => {
-				let (a, b) = local_stack.pop2();
-				local_stack.push((a != 0 || b != 0) as u32);
+
+			match instruction {
+				Instruction::SetLocalColumn => observer.on_column_switch(local_column, self.local_column),
+				Instruction::SetRemoteStack => observer.on_remote_switch(remote_column, self.remote_column),
+				Instruction::Terminate => observer.on_terminate(),
+				_ => {},
 			}
-			Instruction::LogicalNot => {
-				if local_stack.pop() == 0 {
-					local_stack.push(1);
-				} else {
-					local_stack.push(0);
-				}
+		}
+
+		// keep count of the values in every stack
+		self.cells = (self.cells + stack_cells(&local_stack, remote_stack.as_deref())).saturating_sub(cells_before);
+		self.limits.check_stacks(&local_stack, remote_stack.as_deref(), self.cells).map_err(|limit| limit.at(location))?;
+
+		if let Some((local, remote)) = watched.filter(|_| self.debugging.is_watching()) {
+			let mut changes = vec![local.change(&local_stack)];
+
+			if let (Some(remote), Some(remote_stack)) = (remote, &remote_stack) {
+				changes.push(remote.change(remote_stack));
 			}
-			Instruction::Random => {
-				local_stack.push(rand::random());
-			},
-			Instruction::StringMode => {
-				// realistically this will only ever *enable* string mode
-				// because execute_instruction is not called while already
-				// inside string mode
-				self.is_string_mode = !self.is_string_mode;
-			},
+
+			self.stopped = self.debugging.watchpoint(location, instruction, &changes);
+		}
+
+		Ok(())
+	}
+}
+
+/// Box a borrowed reader, which is a reader itself.
+fn box_reader<'a>(reader: Option<&'a mut dyn Read>) -> Option<Box<dyn Read + 'a>> {
+	reader.map(|reader| Box::new(reader) as Box<dyn Read>)
+}
+
+/// Box a borrowed writer, which is a writer itself.
+fn box_writer<'a>(writer: Option<&'a mut dyn Write>) -> Option<Box<dyn Write + 'a>> {
+	writer.map(|writer| Box::new(writer) as Box<dyn Write>)
+}
+
+impl<'a, T: Threading> Io<'a, T> {
+	/// Execute an input or output instruction.
+	///
+	/// What was read or printed is reported to the observer, if any.
+	fn execute<V: Value>(&mut self, instruction: Instruction, local_stack: &mut VecStack<V>, rules: Rules, end_of_input: &V, location: Location, observer: Option<&mut dyn Observer<V>>) -> Result<(), Error> {
+		let (charset, unprintable) = (self.charset, self.unprintable);
+		let handler = self.handler();
+
+		match instruction {
 			Instruction::Input => {
-				if let Some(reader) = &mut self.reader {
-					let mut buffer = [0; 1];
-					reader.read(&mut buffer)?;
+				if handler.has_input() {
+					// anything printed before, such as a prompt, should be seen first
+					handler.flush()?;
+
+					let input = handler.read()?;
+
+					// the recorded run read something else here
+					if self.has_replay_run_out {
+						return Err(Error::ReplayEnded { location });
+					}
+
+					if let Some(observer) = observer {
+						observer.on_input(&input);
+					}
 
-					local_stack.push(buffer[0] as u32);
+					match input {
+						Input::Char(c) => local_stack.push(V::from_u32(c)),
+						Input::Number(number) => local_stack.push(number.parse().unwrap_or_default()),
+						Input::End if rules.strict.empty_input => return Err(Trap::EndOfInput.at(location)),
+						Input::End => local_stack.push(end_of_input.clone()),
+					}
 				}
 			},
 			Instruction::PrintChar => {
-				if let Some(writer) = &mut self.writer {
-					let c = std::char::from_u32(local_stack.pop()).unwrap();
-					write!(writer, "{}", c)?;
+				if handler.has_output() {
+					let value = rules.pop_local(local_stack).map_err(|trap| trap.at(location))?;
+
+					if let Some(c) = printable(&value, charset, unprintable.unwrap_or(Unprintable::Error), location)? {
+						handler.print_char(c)?;
+
+						if let Some(observer) = observer {
+							observer.on_output(&OutputEvent::Char(c));
+						}
+					}
 				}
 			},
 			Instruction::PrintNumber => {
-				if let Some(writer) = &mut self.writer {
-					write!(writer, "{}", local_stack.pop())?;
+				if handler.has_output() {
+					let value = rules.pop_local(local_stack).map_err(|trap| trap.at(location))?;
+					let number = value.to_string();
+					handler.print_number(&number)?;
+
+					if let Some(observer) = observer {
+						observer.on_output(&OutputEvent::Number(number));
+					}
 				}
 			},
 			Instruction::PrintAll => {
-				if let Some(writer) = &mut self.writer {
-					let s = local_stack.values().iter().rev().filter_map(|val| {
-						std::char::from_u32(*val)
-					}).collect::<String>();
+				if handler.has_output() {
+					let mut s = String::new();
+
+					for value in local_stack.values().iter().rev() {
+						s.extend(printable(value, charset, unprintable.unwrap_or(Unprintable::Skip), location)?);
+					}
 
-					write!(writer, "{}", s)?;
+					handler.print_string(&s)?;
+
+					if let Some(observer) = observer {
+						observer.on_output(&OutputEvent::String(s));
+					}
 
 					local_stack.clear();
 				}
 			},
-			Instruction::Terminate => {
-				step_result.is_alive = false;
-			},
+			_ => unreachable!("not an I/O instruction"),
+		}
+
+		Ok(())
+	}
+
+	/// The handler of the input and output, which is the interpreter's own unless a custom one is set.
+	fn handler(&mut self) -> &mut dyn IoHandler {
+		match self.handler {
+			Some(ref mut handler) => handler,
+			None => self,
+		}
+	}
+
+	/// Read a decimal integer, or `None` at the end of the input.
+	fn read_number(&mut self) -> io::Result<Input> {
+		loop {
+			// the number read so far is kept in case the reader would block
+			let c = self.read_char()?;
+
+			match c.and_then(std::char::from_u32) {
+				Some(digit @ '0'..='9') => self.number.push(digit),
+				Some('-') if !self.number.ends_with(|c: char| c.is_ascii_digit()) => self.number = String::from("-"),
+				_ if self.number.ends_with(|c: char| c.is_ascii_digit()) => return Ok(Input::Number(std::mem::take(&mut self.number))),
+				_ if c.is_none() => {
+					self.number.clear();
+					return Ok(Input::End);
+				},
+				_ => self.number.clear(),
+			}
+		}
+	}
+
+	/// Read the code point of the next character, or `None` at the end of the input.
+	fn read_char(&mut self) -> io::Result<Option<u32>> {
+		loop {
+			let byte = match self.decoder.take_peeked() {
+				Some(byte) => Some(byte),
+				None => self.read_byte()?,
+			};
+
+			if let Some(c) = self.decoder.push(self.charset, byte) {
+				return Ok(c);
+			}
+		}
+	}
+
+	/// Read the next byte of input, or `None` at the end of the input.
+	fn read_byte(&mut self) -> io::Result<Option<u8>> {
+		if self.input_mode != InputMode::Line {
+			let mut buffer = [0; 1];
+			let count = self.read_input(&mut buffer)?;
+
+			// nothing read means no more input
+			return Ok(if count == 0 { None } else { Some(buffer[0]) });
+		}
+
+		// wait for a whole line, or the end of the input
+		while !self.line.contains(&b'\n') && !self.is_line_ended {
+			let mut buffer = [0; 256];
+			let count = self.read_input(&mut buffer)?;
+
+			self.line.extend(&buffer[..count]);
+			self.is_line_ended = count == 0;
+		}
+
+		let byte = self.line.pop_front();
+
+		// the input could continue after it ended, as with a terminal
+		if byte.is_none() {
+			self.is_line_ended = false;
+		}
+
+		Ok(byte)
+	}
+
+	/// Read as much input as is available into the buffer, returning how much was read.
+	fn read_input(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+		// a replay takes the place of the reader, as does pushed input
+		let reader = match (&mut self.replay, &mut self.pushed, &mut self.reader) {
+			(Some(replay), _, _) => replay as &mut dyn Read,
+			(None, Some(pushed), _) => pushed as &mut dyn Read,
+			(None, None, Some(reader)) => reader as &mut dyn Read,
+			(None, None, None) => return Ok(0),
 		};
 
+		let count = reader.read(buffer)?;
+		let is_ended = count == 0 && !buffer.is_empty();
+
+		if self.replay.is_some() && is_ended && !self.is_replay_ended {
+			self.has_replay_run_out = true;
+		}
+
+		if let Some(recorded) = &mut self.recorded {
+			recorded.extend_from_slice(&buffer[..count]);
+			self.is_recorded_ended |= is_ended;
+		}
+
+		Ok(count)
+	}
+
+	/// Collect what was printed, or write it if it isn't being collected.
+	fn emit(&mut self, event: OutputEvent) -> io::Result<()> {
+		match (&mut self.events, &mut self.writer) {
+			(Some(events), _) => events.push(event),
+			(None, Some(_)) => {
+				self.charset.encode(&event.to_string(), &mut self.output);
+
+				if self.output.len() >= self.buffer_size {
+					self.flush()?;
+				}
+			},
+			(None, None) => {},
+		}
+
+		Ok(())
+	}
+}
+
+/// The built-in handler, reading from the reader and writing to the writer.
+impl<'a, T: Threading> IoHandler for Io<'a, T> {
+	fn read(&mut self) -> io::Result<Input> {
+		match self.input_mode {
+			InputMode::Number => self.read_number(),
+			InputMode::Char | InputMode::Line => Ok(self.read_char()?.map_or(Input::End, Input::Char)),
+		}
+	}
+
+	fn print_char(&mut self, c: char) -> io::Result<()> {
+		self.emit(OutputEvent::Char(c))
+	}
+
+	fn print_number(&mut self, number: &str) -> io::Result<()> {
+		self.emit(OutputEvent::Number(number.to_string()))
+	}
+
+	fn print_string(&mut self, s: &str) -> io::Result<()> {
+		self.emit(OutputEvent::String(s.to_string()))
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		if let Some(writer) = &mut self.writer {
+			let result = writer.write_all(&self.output);
+
+			// the output is dropped even if it couldn't be written, so it isn't written twice
+			self.output.clear();
+			result?;
+
+			writer.flush()?;
+		}
+
+		Ok(())
+	}
+
+	fn has_input(&self) -> bool {
+		self.replay.is_some() || self.pushed.is_some() || self.reader.is_some()
+	}
+
+	fn has_output(&self) -> bool {
+		self.writer.is_some() || self.events.is_some()
+	}
+}
+
+/// The character a value is printed as, or `None` if nothing is printed.
+fn printable<V: Value>(value: &V, charset: Charset, unprintable: Unprintable, location: Location) -> Result<Option<char>, Error> {
+	match (charset.to_char(value), unprintable) {
+		(Some(c), _) => Ok(Some(c)),
+		(None, Unprintable::Error) => Err(Error::InvalidCodePoint { value: value.to_string(), location }),
+		(None, Unprintable::Replace) => Ok(Some(charset.replacement())),
+		(None, Unprintable::Skip) => Ok(None),
+	}
+}
+
+impl OverflowPolicy {
+	/// Apply the operation matching the policy, resulting in `None` if it should trap.
+	fn apply<V>(self, a: &V, b: &V, wrapping: fn(&V, &V) -> V, saturating: fn(&V, &V) -> V, checked: fn(&V, &V) -> Option<V>) -> Option<V> {
+		match self {
+			OverflowPolicy::Wrap => Some(wrapping(a, b)),
+			OverflowPolicy::Saturate => Some(saturating(a, b)),
+			OverflowPolicy::Trap => checked(a, b),
+		}
+	}
+}
+
+impl Rules {
+	/// Pop a value from the local stack, which is zero if it's empty and that isn't strict.
+	fn pop_local<V: Value>(&self, stack: &mut VecStack<V>) -> Result<V, Trap> {
+		pop_or_trap(stack, self.strict.empty_local_stack, Trap::EmptyLocalStack)
+	}
+
+	/// Pop a value from the remote stack, which is zero if it's empty and that isn't strict.
+	fn pop_remote<V: Value>(&self, stack: &mut VecStack<V>) -> Result<V, Trap> {
+		pop_or_trap(stack, self.strict.empty_remote_stack, Trap::EmptyRemoteStack)
+	}
+
+	/// Check the remote stack is separate from the local one before using it, if that is strict.
+	fn check_remote<V>(&self, remote_stack: &Option<V>) -> Result<(), Trap> {
+		if remote_stack.is_none() && self.strict.uninitialised_remote {
+			return Err(Trap::UninitialisedRemote);
+		}
+
 		Ok(())
 	}
 }
+
+fn pop_or_trap<V: Value>(stack: &mut VecStack<V>, strict: bool, trap: Trap) -> Result<V, Trap> {
+	match stack.try_pop() {
+		Some(value) => Ok(value),
+		None if strict => Err(trap),
+		None => Ok(V::default()),
+	}
+}
+
+/// Execute an instruction which only works with the local and remote stacks.
+///
+/// The remote stack is `None` when it is the local stack, or hasn't been initialized yet.
+fn execute_stack_instruction<V: Value>(instruction: Instruction, local_column: u32, local_stack: &mut VecStack<V>, remote_stack: Option<&mut VecStack<V>>, rules: Rules) -> Result<(), Trap> {
+	match instruction {
+		Instruction::PushLeftIndex => {
+			local_stack.push(V::from_u32(local_column.wrapping_sub(1)));
+		},
+		Instruction::PushRightIndex => {
+			local_stack.push(V::from_u32(local_column.wrapping_add(1)));
+		},
+		Instruction::PushCurrentIndex => {
+			local_stack.push(V::from_u32(local_column));
+		},
+		Instruction::MoveToRemote => {
+			rules.check_remote(&remote_stack)?;
+
+			if let Some(remote_stack) = remote_stack { // redundant otherwise
+				remote_stack.push(rules.pop_local(local_stack)?);
+			}
+		},
+		Instruction::MoveToLocal => {
+			rules.check_remote(&remote_stack)?;
+
+			if let Some(remote_stack) = remote_stack { // redundant otherwise
+				local_stack.push(rules.pop_remote(remote_stack)?);
+			}
+		},
+		Instruction::SwapTop => {
+			let a = rules.pop_local(local_stack)?;
+			let b = rules.pop_local(local_stack)?;
+			local_stack.push(a);
+			local_stack.push(b);
+		},
+		Instruction::DuplicateTop => {
+			let value = local_stack.peek();
+			local_stack.push(value);
+		},
+		Instruction::Discard => {
+			rules.pop_local(local_stack)?;
+		},
+		Instruction::Clear => {
+			local_stack.clear();
+		},
+		Instruction::SwapStacks => {
+			rules.check_remote(&remote_stack)?;
+
+			if let Some(remote_stack) = remote_stack {
+				// TODO could take advantage of RefCell swap
+				VecStack::swap(local_stack, remote_stack);
+			}
+		},
+		Instruction::Reverse => {
+			local_stack.reverse();
+		},
+		Instruction::Value(value) => {
+			local_stack.push(V::from_u32(value));
+		},
+		Instruction::LogicalNot => {
+			let value = rules.pop_local(local_stack)?;
+			local_stack.push(V::from_bool(value.is_zero()));
+		}
+		_ => {
+			let a = rules.pop_local(local_stack)?;
+			let b = rules.pop_local(local_stack)?;
+
+			local_stack.push(execute_binary(instruction, &a, &b, rules)?);
+		},
+	}
+
+	Ok(())
+}
+
+/// Compute the result of a binary instruction, where `a` is the top value and `b` the one below it.
+fn execute_binary<V: Value>(instruction: Instruction, a: &V, b: &V, rules: Rules) -> Result<V, Trap> {
+	let policy = rules.overflow_policy;
+
+	if a.is_zero() && rules.strict.division_by_zero && matches!(instruction, Instruction::Divide | Instruction::Modulo) {
+		return Err(Trap::DivisionByZero);
+	}
+
+	let result = match instruction {
+		Instruction::Add => policy.apply(b, a, V::wrapping_add, V::saturating_add, V::checked_add),
+		Instruction::Subtract => policy.apply(b, a, V::wrapping_sub, V::saturating_sub, V::checked_sub),
+		Instruction::Multiply => policy.apply(b, a, V::wrapping_mul, V::saturating_mul, V::checked_mul),
+		Instruction::Divide => policy.apply(b, a, V::wrapping_div, V::saturating_div, V::checked_div),
+		Instruction::Modulo => policy.apply(b, a, V::wrapping_rem, V::wrapping_rem, V::checked_rem),
+		Instruction::Equals => Some(V::from_bool(b == a)),
+		Instruction::GreaterThan => Some(V::from_bool(b > a)),
+		Instruction::BitwiseNand => Some(a.nand(b)),
+		Instruction::LogicalAnd => Some(V::from_bool(!a.is_zero() && !b.is_zero())),
+		Instruction::LogicalOr => Some(V::from_bool(!a.is_zero() || !b.is_zero())),
+		_ => unreachable!("not a binary instruction"),
+	};
+
+	// only the trap policy has no result
+	result.ok_or_else(|| Trap::Overflow { instruction, a: a.to_string(), b: b.to_string() })
+}
@@ -0,0 +1,346 @@
+//! Watching a program as it runs.
+
+use super::breakpoint::Change;
+use super::{Input, OutputEvent};
+use crate::parser::Instruction;
+use crate::program::VecStack;
+use crate::value::Value;
+
+/// Sees everything a program does, one instruction at a time.
+///
+/// Every method does nothing by default, so only the events which matter have to be handled. Set
+/// an observer with [`Interpreter::with_observer`](struct.Interpreter.html#method.with_observer).
+/// Without one, none of the events are collected, so there is nothing to pay for.
+///
+/// Observing a program executes every step on its own, so the
+/// [bytecode backend](enum.Backend.html) isn't used.
+///
+/// Events about an instruction which fails aren't reported, except for
+/// [`before_instruction`](#method.before_instruction).
+///
+/// Several observers can be used at once by putting them in a tuple, and an `Option` of an
+/// observer only observes anything if it's `Some`.
+///
+/// # Example
+///
+/// ```
+/// use col::interpreter::{Interpreter, Observer};
+/// use col::parser::Instruction;
+/// use col::program::SimpleProgramState;
+///
+/// /// Counts how many times each column is entered with `;`.
+/// #[derive(Default)]
+/// struct Entries(Vec<u32>);
+///
+/// impl Observer<u32> for Entries {
+///     fn on_column_switch(&mut self, _from: u32, to: u32) {
+///         self.0.push(to);
+///     }
+/// }
+///
+/// let mut entries = Entries::default();
+///
+/// Interpreter::<SimpleProgramState>::new("1;\n2;\n@", None, None)
+///     .with_observer(&mut entries)
+///     .run()
+///     .expect("The program failed");
+///
+/// assert_eq!(entries.0, vec![1, 2]);
+/// ```
+pub trait Observer<V> {
+	/// An instruction is about to be executed in a column.
+	///
+	/// While in string mode, this is called for every character pushed, with the `"` which
+	/// started the string. A `_` which [waits for input](enum.ExecutionStatus.html#variant.NeedsInput)
+	/// is only reported once, not every time it's retried.
+	fn before_instruction(&mut self, _column: u32, _ip: u32, _instruction: Instruction) {}
+
+	/// An instruction was executed, leaving the stacks of the local and remote columns, which
+	/// are the ones it switched to if it changed them.
+	///
+	/// There is no remote stack when it is the local stack, or hasn't been created yet.
+	fn after_instruction(&mut self, _local: &[V], _remote: Option<&[V]>) {}
+
+	/// A value was pushed to the stack of a column.
+	fn on_push(&mut self, _column: u32, _value: &V) {}
+
+	/// A value was popped from the stack of a column.
+	///
+	/// Popping an empty stack, which gives zero, isn't reported. Instructions which change a whole
+	/// stack, such as `r`, `c` or `s`, pop every value and push the new ones.
+	fn on_pop(&mut self, _column: u32, _value: &V) {}
+
+	/// `;` started executing another column.
+	fn on_column_switch(&mut self, _from: u32, _to: u32) {}
+
+	/// `~` changed the remote stack.
+	fn on_remote_switch(&mut self, _from: u32, _to: u32) {}
+
+	/// `_` read some input.
+	fn on_input(&mut self, _input: &Input) {}
+
+	/// `$`, `#` or `p` printed something.
+	fn on_output(&mut self, _output: &OutputEvent) {}
+
+	/// `@` terminated the program.
+	fn on_terminate(&mut self) {}
+}
+
+impl<V, O: Observer<V> + ?Sized> Observer<V> for &mut O {
+	fn before_instruction(&mut self, column: u32, ip: u32, instruction: Instruction) {
+		(**self).before_instruction(column, ip, instruction)
+	}
+
+	fn after_instruction(&mut self, local: &[V], remote: Option<&[V]>) {
+		(**self).after_instruction(local, remote)
+	}
+
+	fn on_push(&mut self, column: u32, value: &V) {
+		(**self).on_push(column, value)
+	}
+
+	fn on_pop(&mut self, column: u32, value: &V) {
+		(**self).on_pop(column, value)
+	}
+
+	fn on_column_switch(&mut self, from: u32, to: u32) {
+		(**self).on_column_switch(from, to)
+	}
+
+	fn on_remote_switch(&mut self, from: u32, to: u32) {
+		(**self).on_remote_switch(from, to)
+	}
+
+	fn on_input(&mut self, input: &Input) {
+		(**self).on_input(input)
+	}
+
+	fn on_output(&mut self, output: &OutputEvent) {
+		(**self).on_output(output)
+	}
+
+	fn on_terminate(&mut self) {
+		(**self).on_terminate()
+	}
+}
+
+impl<V, O: Observer<V> + ?Sized> Observer<V> for Box<O> {
+	fn before_instruction(&mut self, column: u32, ip: u32, instruction: Instruction) {
+		(**self).before_instruction(column, ip, instruction)
+	}
+
+	fn after_instruction(&mut self, local: &[V], remote: Option<&[V]>) {
+		(**self).after_instruction(local, remote)
+	}
+
+	fn on_push(&mut self, column: u32, value: &V) {
+		(**self).on_push(column, value)
+	}
+
+	fn on_pop(&mut self, column: u32, value: &V) {
+		(**self).on_pop(column, value)
+	}
+
+	fn on_column_switch(&mut self, from: u32, to: u32) {
+		(**self).on_column_switch(from, to)
+	}
+
+	fn on_remote_switch(&mut self, from: u32, to: u32) {
+		(**self).on_remote_switch(from, to)
+	}
+
+	fn on_input(&mut self, input: &Input) {
+		(**self).on_input(input)
+	}
+
+	fn on_output(&mut self, output: &OutputEvent) {
+		(**self).on_output(output)
+	}
+
+	fn on_terminate(&mut self) {
+		(**self).on_terminate()
+	}
+}
+
+/// Nothing is observed when there is no observer.
+impl<V, O: Observer<V>> Observer<V> for Option<O> {
+	fn before_instruction(&mut self, column: u32, ip: u32, instruction: Instruction) {
+		if let Some(observer) = self {
+			observer.before_instruction(column, ip, instruction);
+		}
+	}
+
+	fn after_instruction(&mut self, local: &[V], remote: Option<&[V]>) {
+		if let Some(observer) = self {
+			observer.after_instruction(local, remote);
+		}
+	}
+
+	fn on_push(&mut self, column: u32, value: &V) {
+		if let Some(observer) = self {
+			observer.on_push(column, value);
+		}
+	}
+
+	fn on_pop(&mut self, column: u32, value: &V) {
+		if let Some(observer) = self {
+			observer.on_pop(column, value);
+		}
+	}
+
+	fn on_column_switch(&mut self, from: u32, to: u32) {
+		if let Some(observer) = self {
+			observer.on_column_switch(from, to);
+		}
+	}
+
+	fn on_remote_switch(&mut self, from: u32, to: u32) {
+		if let Some(observer) = self {
+			observer.on_remote_switch(from, to);
+		}
+	}
+
+	fn on_input(&mut self, input: &Input) {
+		if let Some(observer) = self {
+			observer.on_input(input);
+		}
+	}
+
+	fn on_output(&mut self, output: &OutputEvent) {
+		if let Some(observer) = self {
+			observer.on_output(output);
+		}
+	}
+
+	fn on_terminate(&mut self) {
+		if let Some(observer) = self {
+			observer.on_terminate();
+		}
+	}
+}
+
+/// Several observers can be used at once as a tuple, each seeing every event in order.
+macro_rules! tuple_observer {
+	($($name:ident $index:tt),+) => {
+		impl<V, $($name: Observer<V>),+> Observer<V> for ($($name,)+) {
+			fn before_instruction(&mut self, column: u32, ip: u32, instruction: Instruction) {
+				$(self.$index.before_instruction(column, ip, instruction);)+
+			}
+
+			fn after_instruction(&mut self, local: &[V], remote: Option<&[V]>) {
+				$(self.$index.after_instruction(local, remote);)+
+			}
+
+			fn on_push(&mut self, column: u32, value: &V) {
+				$(self.$index.on_push(column, value);)+
+			}
+
+			fn on_pop(&mut self, column: u32, value: &V) {
+				$(self.$index.on_pop(column, value);)+
+			}
+
+			fn on_column_switch(&mut self, from: u32, to: u32) {
+				$(self.$index.on_column_switch(from, to);)+
+			}
+
+			fn on_remote_switch(&mut self, from: u32, to: u32) {
+				$(self.$index.on_remote_switch(from, to);)+
+			}
+
+			fn on_input(&mut self, input: &Input) {
+				$(self.$index.on_input(input);)+
+			}
+
+			fn on_output(&mut self, output: &OutputEvent) {
+				$(self.$index.on_output(output);)+
+			}
+
+			fn on_terminate(&mut self) {
+				$(self.$index.on_terminate();)+
+			}
+		}
+	};
+}
+
+tuple_observer!(A 0, B 1);
+tuple_observer!(A 0, B 1, C 2);
+tuple_observer!(A 0, B 1, C 2, D 3);
+
+/// The top of a stack before an instruction, to tell which values it popped and pushed.
+pub(super) struct Watched<V> {
+	column: u32,
+	/// How many values can't be touched by the instruction
+	base: usize,
+	/// The values which can be
+	values: Vec<V>,
+}
+
+impl<V: Value> Watched<V> {
+	/// Keep the values of the stack which could be popped, or all of them if `depth` is `None`.
+	pub(super) fn new(column: u32, stack: &VecStack<V>, depth: Option<usize>) -> Watched<V> {
+		let values = stack.values();
+		let base = depth.map_or(0, |depth| values.len().saturating_sub(depth));
+
+		Watched { column, base, values: values[base..].to_vec() }
+	}
+
+	/// Report the values which were there before as popped, and those there now as pushed.
+	pub(super) fn report(&self, stack: &VecStack<V>, observer: &mut dyn Observer<V>) {
+		for value in self.values.iter().rev() {
+			observer.on_pop(self.column, value);
+		}
+
+		for value in stack.values().get(self.base..).unwrap_or_default() {
+			observer.on_push(self.column, value);
+		}
+	}
+
+	/// How many values were popped and pushed, counted the same way as they're reported.
+	pub(super) fn change(&self, stack: &VecStack<V>) -> Change {
+		let after = stack.len();
+
+		Change {
+			column: self.column,
+			popped: self.values.len(),
+			pushed: after.saturating_sub(self.base),
+			before: self.base + self.values.len(),
+			after,
+		}
+	}
+}
+
+/// How many values an instruction can pop from the local stack, or `None` for all of them.
+pub(super) fn local_depth(instruction: Instruction) -> Option<usize> {
+	match instruction {
+		Instruction::Clear | Instruction::Reverse | Instruction::SwapStacks | Instruction::PrintAll => None,
+		Instruction::SwapTop
+		| Instruction::Add
+		| Instruction::Subtract
+		| Instruction::Multiply
+		| Instruction::Divide
+		| Instruction::Modulo
+		| Instruction::Equals
+		| Instruction::GreaterThan
+		| Instruction::BitwiseNand
+		| Instruction::LogicalAnd
+		| Instruction::LogicalOr => Some(2),
+		Instruction::SetLocalColumn
+		| Instruction::SetRemoteStack
+		| Instruction::MoveToRemote
+		| Instruction::Discard
+		| Instruction::LogicalNot
+		| Instruction::PrintChar
+		| Instruction::PrintNumber => Some(1),
+		_ => Some(0),
+	}
+}
+
+/// How many values an instruction can pop from the remote stack, or `None` if it doesn't touch it.
+pub(super) fn remote_depth(instruction: Instruction) -> Option<Option<usize>> {
+	match instruction {
+		Instruction::MoveToRemote => Some(Some(0)),
+		Instruction::MoveToLocal => Some(Some(1)),
+		Instruction::SwapStacks => Some(None),
+		_ => None,
+	}
+}
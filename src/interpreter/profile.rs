@@ -0,0 +1,263 @@
+//! Counting where a program spends its steps.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use super::Observer;
+use crate::parser::{Instruction, Program};
+
+/// How many columns entered with `;` are kept in a chain for the folded stacks.
+const CHAIN_DEPTH: usize = 16;
+
+/// An [observer](trait.Observer.html) counting how many times each instruction is executed, and
+/// how often each column switches to another with `;`.
+///
+/// The counts can be reported as hot spots and annotated source with
+/// [`write_report`](#method.write_report), or as folded stacks for flame graph tools with
+/// [`write_folded`](#method.write_folded).
+///
+/// # Example
+///
+/// ```
+/// use col::interpreter::{Interpreter, Profiler};
+/// use col::parser;
+/// use col::program::SimpleProgramState;
+///
+/// let program = parser::parse("5[1-:]1;\n@");
+/// let mut profiler = Profiler::default();
+///
+/// Interpreter::<SimpleProgramState>::from_program(&program, None, None)
+///     .with_observer(&mut profiler)
+///     .run()
+///     .expect("The program failed");
+///
+/// // the loop ran five times
+/// assert_eq!(profiler.count(0, 2), 5);
+/// assert_eq!(profiler.transitions(), vec![(0, 1, 1)]);
+///
+/// let mut report = Vec::new();
+/// profiler.write_report(&program, 10, &mut report).expect("Could not write the report");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Profiler {
+	/// How many times each instruction of each column was executed
+	counts: Vec<Vec<u64>>,
+	/// How many times each column switched to another
+	transitions: HashMap<(u32, u32), u64>,
+	/// Every chain of columns entered so far, ending with the current column
+	chains: Vec<Vec<u32>>,
+	/// The index of each chain
+	chain_indices: HashMap<Vec<u32>, usize>,
+	/// How many steps were executed in each chain
+	chain_counts: Vec<u64>,
+	/// The chain of the current column
+	chain: usize,
+}
+
+impl Profiler {
+	/// How many times the instruction at `ip` in a column was executed.
+	///
+	/// Every character pushed in string mode counts for the `"` which started the string.
+	pub fn count(&self, column: u32, ip: u32) -> u64 {
+		self.counts.get(column as usize)
+			.and_then(|counts| counts.get(ip as usize))
+			.copied()
+			.unwrap_or(0)
+	}
+
+	/// How many steps were executed in total.
+	pub fn steps(&self) -> u64 {
+		self.chain_counts.iter().sum()
+	}
+
+	/// How many steps were executed in a column.
+	pub fn column_steps(&self, column: u32) -> u64 {
+		self.counts.get(column as usize).map_or(0, |counts| counts.iter().sum())
+	}
+
+	/// How many times each column switched to another with `;`, as `(from, to, count)`, most
+	/// frequent first.
+	pub fn transitions(&self) -> Vec<(u32, u32, u64)> {
+		let mut transitions: Vec<_> = self.transitions.iter()
+			.map(|((from, to), count)| (*from, *to, *count))
+			.collect();
+
+		transitions.sort_by(|a, b| b.2.cmp(&a.2).then((a.0, a.1).cmp(&(b.0, b.1))));
+		transitions
+	}
+
+	/// The most executed instructions, as `(column, ip, count)`, most frequent first.
+	pub fn hot_spots(&self, count: usize) -> Vec<(u32, u32, u64)> {
+		let mut spots: Vec<_> = self.counts.iter().enumerate()
+			.flat_map(|(column, counts)| {
+				counts.iter().enumerate()
+					.filter(|(_, count)| **count > 0)
+					.map(move |(ip, count)| (column as u32, ip as u32, *count))
+			})
+			.collect();
+
+		spots.sort_by(|a, b| b.2.cmp(&a.2).then((a.0, a.1).cmp(&(b.0, b.1))));
+		spots.truncate(count);
+		spots
+	}
+
+	/// How many times each kind of instruction was executed, such as `Add` or `Value`, most
+	/// frequent first.
+	pub fn instruction_counts(&self, program: &Program) -> Vec<(String, u64)> {
+		let mut counts: HashMap<String, u64> = HashMap::new();
+
+		for (column, source) in program.columns().iter().enumerate() {
+			for (ip, instruction) in source.instructions().iter().enumerate() {
+				let count = self.count(column as u32, ip as u32);
+
+				if count > 0 {
+					*counts.entry(kind(*instruction)).or_default() += count;
+				}
+			}
+		}
+
+		let mut counts: Vec<_> = counts.into_iter().collect();
+		counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+		counts
+	}
+
+	/// Write the hot spots, instruction counts, column transitions and the source annotated with
+	/// how many times each instruction was executed.
+	pub fn write_report<W: Write>(&self, program: &Program, hot_spots: usize, mut writer: W) -> io::Result<()> {
+		let steps = self.steps().max(1);
+		let percent = |count: u64| count as f64 * 100.0 / steps as f64;
+
+		writeln!(writer, "{} steps", self.steps())?;
+		writeln!(writer)?;
+		writeln!(writer, "hot spots:")?;
+
+		for (column, ip, count) in self.hot_spots(hot_spots) {
+			let instruction = program.column(column).and_then(|source| source.instruction(ip));
+			let c = instruction.map_or(' ', |instruction| instruction.to_char());
+
+			writeln!(writer, "{:>12} {:>6.2}%  {}:{}  {}", count, percent(count), column, ip, c)?;
+		}
+
+		writeln!(writer)?;
+		writeln!(writer, "instructions:")?;
+
+		for (kind, count) in self.instruction_counts(program) {
+			writeln!(writer, "{:>12} {:>6.2}%  {}", count, percent(count), kind)?;
+		}
+
+		writeln!(writer)?;
+		writeln!(writer, "column transitions:")?;
+
+		for (from, to, count) in self.transitions() {
+			writeln!(writer, "{:>12}  {} -> {}", count, from, to)?;
+		}
+
+		writeln!(writer)?;
+		writeln!(writer, "annotated source:")?;
+
+		for (column, source) in program.columns().iter().enumerate() {
+			let column = column as u32;
+
+			writeln!(writer)?;
+			writeln!(writer, "column {} ({} steps): {}", column, self.column_steps(column), source.source())?;
+
+			for ip in 0..source.len() {
+				let count = self.count(column, ip);
+
+				if count > 0 {
+					let c = source.instructions()[ip as usize].to_char();
+					writeln!(writer, "{:>12}  {:>4}  {}", count, source.offset(ip).unwrap(), c)?;
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Write the steps executed in each chain of columns entered with `;`, in the folded stack
+	/// format of flame graph tools, with a line such as `column 0;column 2 1234` for each chain.
+	///
+	/// Switching to a column which is already in the chain goes back to it, as if returning from
+	/// the columns after it, so loops between columns don't make the chain longer. Only the last
+	/// columns of a long chain are kept.
+	pub fn write_folded<W: Write>(&self, mut writer: W) -> io::Result<()> {
+		for (chain, count) in self.chains.iter().zip(&self.chain_counts) {
+			if *count == 0 {
+				continue;
+			}
+
+			let frames: Vec<String> = chain.iter().map(|column| format!("column {}", column)).collect();
+			writeln!(writer, "{} {}", frames.join(";"), count)?;
+		}
+
+		Ok(())
+	}
+
+	/// The index of a chain, adding it if it's new.
+	fn intern(&mut self, chain: Vec<u32>) -> usize {
+		if let Some(index) = self.chain_indices.get(&chain) {
+			return *index;
+		}
+
+		let index = self.chains.len();
+		self.chains.push(chain.clone());
+		self.chain_indices.insert(chain, index);
+		self.chain_counts.push(0);
+		index
+	}
+}
+
+impl<V> Observer<V> for Profiler {
+	fn before_instruction(&mut self, column: u32, ip: u32, _instruction: Instruction) {
+		let (column, ip) = (column as usize, ip as usize);
+
+		if column >= self.counts.len() {
+			self.counts.resize_with(column + 1, Vec::new);
+		}
+
+		let counts = &mut self.counts[column];
+
+		if ip >= counts.len() {
+			counts.resize(ip + 1, 0);
+		}
+
+		counts[ip] += 1;
+
+		// the first chain is only known once the first instruction is
+		if self.chains.is_empty() {
+			self.chain = self.intern(vec![column as u32]);
+		}
+
+		self.chain_counts[self.chain] += 1;
+	}
+
+	fn on_column_switch(&mut self, from: u32, to: u32) {
+		*self.transitions.entry((from, to)).or_default() += 1;
+
+		let mut chain = self.chains[self.chain].clone();
+
+		// going back to a column in the chain is like returning to it, which keeps loops short
+		match chain.iter().position(|column| *column == to) {
+			Some(index) => chain.truncate(index + 1),
+			None => {
+				chain.push(to);
+
+				if chain.len() > CHAIN_DEPTH {
+					chain.remove(0);
+				}
+			},
+		}
+
+		self.chain = self.intern(chain);
+	}
+}
+
+/// The name of the kind of an instruction, without its value.
+fn kind(instruction: Instruction) -> String {
+	let name = format!("{:?}", instruction);
+
+	match name.find('(') {
+		Some(end) => name[..end].to_string(),
+		None => name,
+	}
+}
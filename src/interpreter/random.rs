@@ -0,0 +1,128 @@
+//! Where the values pushed by `?` come from.
+
+use rand::{Error, RngCore, SeedableRng};
+
+use super::replay::{Recorder, ReplayRng};
+use super::threading::Threading;
+use crate::value::Value;
+
+/// The random number generator used unless another one is given, a small and fast SplitMix64.
+///
+/// It is not cryptographically secure, but col programs don't need that, and its whole state is a
+/// single number.
+///
+/// # Example
+///
+/// ```
+/// use col::interpreter::DefaultRng;
+/// use rand::{RngCore, SeedableRng};
+///
+/// let mut a = DefaultRng::seed_from_u64(42);
+/// let mut b = DefaultRng::seed_from_u64(42);
+///
+/// assert_eq!(a.next_u64(), b.next_u64());
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DefaultRng {
+	state: u64,
+}
+
+impl RngCore for DefaultRng {
+	fn next_u32(&mut self) -> u32 {
+		(self.next_u64() >> 32) as u32
+	}
+
+	fn next_u64(&mut self) -> u64 {
+		self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+
+		let mut z = self.state;
+		z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+		z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+		z ^ (z >> 31)
+	}
+
+	fn fill_bytes(&mut self, dest: &mut [u8]) {
+		for chunk in dest.chunks_mut(8) {
+			let bytes = self.next_u64().to_le_bytes();
+			chunk.copy_from_slice(&bytes[..chunk.len()]);
+		}
+	}
+
+	fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+		self.fill_bytes(dest);
+		Ok(())
+	}
+}
+
+impl DefaultRng {
+	/// The whole state of the generator, which it can be recreated from with `seed_from_u64`.
+	pub(super) fn state(&self) -> u64 {
+		self.state
+	}
+}
+
+impl SeedableRng for DefaultRng {
+	type Seed = [u8; 8];
+
+	fn from_seed(seed: Self::Seed) -> Self {
+		DefaultRng { state: u64::from_le_bytes(seed) }
+	}
+
+	fn seed_from_u64(state: u64) -> Self {
+		DefaultRng { state }
+	}
+}
+
+/// A random number generator the interpreter owns.
+type BoxedRng<'a, T> = Box<<T as Threading>::Rng<'a>>;
+
+/// The random number generator of an interpreter.
+#[derive(Default)]
+pub(super) struct Random<'a, T: Threading> {
+	pub(super) generator: Generator<'a, T>,
+	/// Every byte generated so far, if recording
+	pub(super) recorded: Option<Vec<u8>>,
+}
+
+#[derive(Default)]
+pub(super) enum Generator<'a, T: Threading> {
+	/// Nothing random has been needed yet, so no seed has been chosen
+	#[default]
+	Unseeded,
+	Default(DefaultRng),
+	Custom(BoxedRng<'a, T>),
+	Replay(ReplayRng),
+}
+
+impl<'a, T: Threading> Random<'a, T> {
+	/// Generate a random value, seeding the default generator first if needed.
+	///
+	/// The seed is zero if the interpreter is deterministic, and comes from the operating system
+	/// otherwise. There is no value once a replay runs out.
+	pub(super) fn next<V: Value>(&mut self, deterministic: bool) -> Option<V> {
+		if let Generator::Unseeded = self.generator {
+			self.generator = Generator::Default(if deterministic {
+				DefaultRng::seed_from_u64(0)
+			} else {
+				DefaultRng::from_entropy()
+			});
+		}
+
+		let rng: &mut dyn RngCore = match &mut self.generator {
+			Generator::Unseeded => unreachable!(),
+			Generator::Default(rng) => rng,
+			Generator::Custom(rng) => rng,
+			Generator::Replay(rng) => rng,
+		};
+
+		let value = match &mut self.recorded {
+			Some(recorded) => V::random(&mut Recorder { rng, recorded }),
+			None => V::random(rng),
+		};
+
+		match &self.generator {
+			Generator::Replay(rng) if rng.has_run_out => None,
+			_ => Some(value),
+		}
+	}
+}
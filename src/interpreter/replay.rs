@@ -0,0 +1,245 @@
+//! Recording everything a program reads from outside, so a run can be repeated exactly.
+
+use std::io::{self, Read, Write};
+
+use rand::{Error, RngCore};
+
+use crate::parser::Program;
+
+/// The version of the replay file format written by [`Recording::write_to`](struct.Recording.html#method.write_to).
+const VERSION: u32 = 2;
+
+/// Everything a program read from outside while it ran: its input, and the random values of `?`.
+///
+/// Record a run with
+/// [`Interpreter::with_recording`](struct.Interpreter.html#method.with_recording), and repeat it
+/// with [`Interpreter::with_replay`](struct.Interpreter.html#method.with_replay).
+///
+/// A recording also identifies the program and the options which change how the input is read,
+/// so replaying it any other way fails instead of quietly doing something else.
+///
+/// # Example
+///
+/// ```
+/// use col::interpreter::Interpreter;
+/// use col::program::SimpleProgramState;
+///
+/// let mut input = "a".as_bytes();
+/// let mut output = Vec::new();
+///
+/// let recording = {
+///     let mut interpreter = Interpreter::<SimpleProgramState>::new("_?#$@", Some(&mut input), Some(&mut output))
+///         .with_recording(true);
+///
+///     interpreter.run().expect("The program failed");
+///     interpreter.recording().unwrap()
+/// };
+///
+/// let mut replayed = Vec::new();
+/// Interpreter::<SimpleProgramState>::new("_?#$@", None, Some(&mut replayed))
+///     .with_replay(recording)
+///     .run()
+///     .expect("The program failed");
+///
+/// assert_eq!(output, replayed);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Recording {
+	/// A hash of the source of the program which was recorded
+	pub program: u64,
+	/// The cell type, charset and input mode of the run
+	pub options: String,
+	/// Every byte of input read by `_`
+	pub input: Vec<u8>,
+	/// Whether the input ended after those bytes
+	pub is_input_ended: bool,
+	/// Every byte produced by the random number generator for `?`
+	pub random: Vec<u8>,
+}
+
+impl Recording {
+	/// Write the recording as a replay file.
+	///
+	/// The file starts with a `col-replay <version>` line, then `program <hash>`, `options <options>`
+	/// and `input-ended <0 or 1>` lines, followed by an `input` and a `random` section. Each
+	/// section is a `<name> <length>` line, then that many raw bytes and a line feed.
+	pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+		writeln!(writer, "col-replay {}", VERSION)?;
+		writeln!(writer, "program {:016x}", self.program)?;
+		writeln!(writer, "options {}", self.options)?;
+		writeln!(writer, "input-ended {}", self.is_input_ended as u8)?;
+
+		for (name, bytes) in &[("input", &self.input), ("random", &self.random)] {
+			writeln!(writer, "{} {}", name, bytes.len())?;
+			writer.write_all(bytes)?;
+			writeln!(writer)?;
+		}
+
+		Ok(())
+	}
+
+	/// Read a replay file written by [`write_to`](#method.write_to).
+	pub fn read_from<R: Read>(mut reader: R) -> io::Result<Recording> {
+		let mut bytes = Vec::new();
+		reader.read_to_end(&mut bytes)?;
+
+		let mut rest = bytes.as_slice();
+
+		let version = read_line(&mut rest)?;
+		if version != format!("col-replay {}", VERSION) {
+			return Err(invalid("not a replay file, or an unsupported version"));
+		}
+
+		let program = u64::from_str_radix(read_field(&mut rest, "program")?, 16).map_err(|_| invalid("invalid program hash"))?;
+		let options = read_field(&mut rest, "options")?.to_string();
+
+		let is_input_ended = match read_field(&mut rest, "input-ended")? {
+			"0" => false,
+			"1" => true,
+			_ => return Err(invalid("invalid end of input")),
+		};
+
+		Ok(Recording {
+			program,
+			options,
+			input: read_section(&mut rest, "input")?,
+			is_input_ended,
+			random: read_section(&mut rest, "random")?,
+		})
+	}
+}
+
+/// A hash of the source of a program, which stays the same across versions and platforms.
+pub(super) fn fingerprint(program: &Program) -> u64 {
+	// FNV-1a
+	let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+	for (index, column) in program.columns().iter().enumerate() {
+		let separator = if index == 0 { &[][..] } else { &b"\n"[..] };
+
+		for byte in separator.iter().chain(column.source().as_bytes()) {
+			hash ^= *byte as u64;
+			hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+		}
+	}
+
+	hash
+}
+
+pub(super) fn invalid(message: &str) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Read up to the next line feed, which is skipped.
+fn read_line<'a>(rest: &mut &'a [u8]) -> io::Result<&'a str> {
+	let end = rest.iter().position(|b| *b == b'\n').ok_or_else(|| invalid("unexpected end of file"))?;
+	let line = std::str::from_utf8(&rest[..end]).map_err(|_| invalid("invalid header"))?;
+
+	*rest = &rest[end + 1..];
+	Ok(line)
+}
+
+/// Read a `<name> <value>` line, returning the value.
+fn read_field<'a>(rest: &mut &'a [u8], name: &str) -> io::Result<&'a str> {
+	match read_line(rest)?.split_once(' ') {
+		Some((found, value)) if found == name => Ok(value),
+		_ => Err(invalid("missing header")),
+	}
+}
+
+fn read_section(rest: &mut &[u8], name: &str) -> io::Result<Vec<u8>> {
+	let header = read_line(rest)?;
+
+	let len = match header.split_once(' ') {
+		Some((found, len)) if found == name => len.parse::<usize>().map_err(|_| invalid("invalid section length"))?,
+		_ => return Err(invalid("missing section")),
+	};
+
+	// the bytes are followed by a line feed
+	if rest.len() <= len || rest[len] != b'\n' {
+		return Err(invalid("unexpected end of file"));
+	}
+
+	let bytes = rest[..len].to_vec();
+	*rest = &rest[len + 1..];
+	Ok(bytes)
+}
+
+/// A random number generator repeating recorded bytes, and zeros once they run out.
+#[derive(Clone, Debug)]
+pub(super) struct ReplayRng {
+	bytes: io::Cursor<Vec<u8>>,
+	/// Whether more bytes were needed than were recorded
+	pub(super) has_run_out: bool,
+}
+
+impl ReplayRng {
+	pub(super) fn new(bytes: Vec<u8>) -> Self {
+		ReplayRng { bytes: io::Cursor::new(bytes), has_run_out: false }
+	}
+
+	/// The bytes which haven't been repeated yet.
+	pub(super) fn remaining(&self) -> &[u8] {
+		let position = (self.bytes.position() as usize).min(self.bytes.get_ref().len());
+		&self.bytes.get_ref()[position..]
+	}
+}
+
+impl RngCore for ReplayRng {
+	fn next_u32(&mut self) -> u32 {
+		let mut bytes = [0; 4];
+		self.fill_bytes(&mut bytes);
+		u32::from_le_bytes(bytes)
+	}
+
+	fn next_u64(&mut self) -> u64 {
+		let mut bytes = [0; 8];
+		self.fill_bytes(&mut bytes);
+		u64::from_le_bytes(bytes)
+	}
+
+	fn fill_bytes(&mut self, dest: &mut [u8]) {
+		let count = self.bytes.read(dest).unwrap_or(0);
+		self.has_run_out |= count < dest.len();
+
+		for byte in &mut dest[count..] {
+			*byte = 0;
+		}
+	}
+
+	fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+		self.fill_bytes(dest);
+		Ok(())
+	}
+}
+
+/// A random number generator keeping a copy of every byte it produces.
+pub(super) struct Recorder<'r, R: RngCore + ?Sized> {
+	pub(super) rng: &'r mut R,
+	pub(super) recorded: &'r mut Vec<u8>,
+}
+
+impl<'r, R: RngCore + ?Sized> RngCore for Recorder<'r, R> {
+	fn next_u32(&mut self) -> u32 {
+		let value = self.rng.next_u32();
+		self.recorded.extend_from_slice(&value.to_le_bytes());
+		value
+	}
+
+	fn next_u64(&mut self) -> u64 {
+		let value = self.rng.next_u64();
+		self.recorded.extend_from_slice(&value.to_le_bytes());
+		value
+	}
+
+	fn fill_bytes(&mut self, dest: &mut [u8]) {
+		self.rng.fill_bytes(dest);
+		self.recorded.extend_from_slice(dest);
+	}
+
+	fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+		self.rng.try_fill_bytes(dest)?;
+		self.recorded.extend_from_slice(dest);
+		Ok(())
+	}
+}
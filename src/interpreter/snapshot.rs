@@ -0,0 +1,444 @@
+//! Saving everything an interpreter holds, so a program can be continued later.
+
+use std::io::{self, Cursor, Read, Write};
+use std::str::{FromStr, Lines};
+
+use rand::SeedableRng;
+
+use super::charset::Decoder;
+use super::events::PushedInput;
+use super::random::{DefaultRng, Generator};
+use super::replay::{invalid, ReplayRng};
+use super::{Interpreter, Threading};
+use crate::error::Error;
+use crate::parser::Instruction;
+use crate::program::ProgramState;
+use crate::value::Value;
+
+/// The version of the state file format written by [`Snapshot::write_to`](struct.Snapshot.html#method.write_to).
+const VERSION: u32 = 1;
+
+/// Everything an interpreter holds while running a program: the stacks, where it is in the
+/// program, how many steps it has executed, the state of its random number generator and the
+/// input it has read but the program hasn't yet.
+///
+/// The program itself isn't part of a snapshot, nor is anything configured with the builder
+/// methods, such as limits or the reader and writer. A custom random number generator given with
+/// [`with_rng`](struct.Interpreter.html#method.with_rng) isn't saved either, and is kept as it is
+/// when restoring, as is the reader. What's left of a [replay](struct.Interpreter.html#method.with_replay)
+/// or of [pushed input](struct.Interpreter.html#method.push_input) is saved though, and takes the
+/// place of the reader when restoring.
+///
+/// # Example
+///
+/// ```
+/// use col::interpreter::{Interpreter, Snapshot};
+/// use col::program::SimpleProgramState;
+///
+/// let source = "1+:#A$";
+/// let mut output = Vec::new();
+/// let mut file = Vec::new();
+///
+/// {
+///     let mut interpreter = Interpreter::<SimpleProgramState>::new(source, None, Some(&mut output));
+///     interpreter.run_for(12);
+///     interpreter.snapshot().write_to(&mut file).expect("Could not save the state");
+/// }
+///
+/// let mut restored = Interpreter::<SimpleProgramState>::new(source, None, Some(&mut output));
+/// restored.restore(Snapshot::read_from(file.as_slice()).expect("Could not load the state"))
+///     .expect("The snapshot doesn't fit");
+/// restored.run_for(6);
+/// drop(restored);
+///
+/// // the restored interpreter continues counting where the first one stopped
+/// assert_eq!(output, b"1\n2\n3\n");
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot<V> {
+	/// The values of every stack, in order of index
+	stacks: Vec<(u32, Vec<V>)>,
+	local_column: u32,
+	remote_column: u32,
+	ip: u32,
+	is_string_mode: bool,
+	string_offset: u32,
+	steps: u64,
+	gc_count: u32,
+	is_terminated: bool,
+	random: RandomState,
+	input: InputState,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum RandomState {
+	/// The default generator hasn't been seeded yet
+	Unseeded,
+	Default(u64),
+	/// The recorded bytes which haven't been repeated yet
+	Replay(Vec<u8>),
+	/// A custom generator, which can't be saved
+	Custom,
+}
+
+/// The input which has been read, but not yet by the program.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct InputState {
+	source: InputSource,
+	/// The start of a character
+	partial: Vec<u8>,
+	/// A byte which belongs to the next character
+	peeked: Option<u8>,
+	/// The rest of the line being read, in the line input mode
+	line: Vec<u8>,
+	is_line_ended: bool,
+	/// The digits of the number being read, in the number input mode
+	number: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+enum InputSource {
+	/// The reader, which can't be saved
+	#[default]
+	Reader,
+	/// The recorded bytes which haven't been read yet
+	Replay { remaining: Vec<u8>, is_ended: bool, has_run_out: bool },
+	/// The pushed bytes which haven't been read yet
+	Pushed { remaining: Vec<u8>, is_closed: bool },
+}
+
+impl<V: Value> Snapshot<V> {
+	/// Whether the program had terminated.
+	pub fn is_terminated(&self) -> bool {
+		self.is_terminated
+	}
+
+	/// Start the program again from the beginning of the first column, keeping the stacks, step
+	/// count and random state.
+	///
+	/// This lets the stacks persist between runs of a program which terminates.
+	pub fn restart(&mut self) {
+		self.local_column = 0;
+		self.remote_column = 0;
+		self.ip = 0;
+		self.is_string_mode = false;
+		self.string_offset = 0;
+		self.is_terminated = false;
+	}
+
+	/// Write the snapshot as a state file.
+	///
+	/// The file is text, starting with a `col-state <version>` line. Every following line is a
+	/// name and its values separated by spaces, with a `stack <index> <values>...` line for each
+	/// stack. Bytes are written in hexadecimal.
+	pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+		writeln!(writer, "col-state {}", VERSION)?;
+		writeln!(writer, "position {} {} {}", self.local_column, self.remote_column, self.ip)?;
+		writeln!(writer, "string {} {}", self.is_string_mode as u8, self.string_offset)?;
+		writeln!(writer, "steps {} {}", self.steps, self.gc_count)?;
+		writeln!(writer, "terminated {}", self.is_terminated as u8)?;
+
+		match &self.random {
+			RandomState::Unseeded => writeln!(writer, "random unseeded")?,
+			RandomState::Default(state) => writeln!(writer, "random default {}", state)?,
+			RandomState::Replay(bytes) => writeln!(writer, "random replay {}", hex(bytes))?,
+			RandomState::Custom => writeln!(writer, "random custom")?,
+		}
+
+		let input = &self.input;
+
+		match &input.source {
+			InputSource::Reader => writeln!(writer, "input reader")?,
+			InputSource::Replay { remaining, is_ended, has_run_out } =>
+				writeln!(writer, "input replay {} {} {}", *is_ended as u8, *has_run_out as u8, hex(remaining))?,
+			InputSource::Pushed { remaining, is_closed } => writeln!(writer, "input pushed {} {}", *is_closed as u8, hex(remaining))?,
+		}
+
+		match (input.peeked, input.partial.is_empty()) {
+			(Some(byte), _) => writeln!(writer, "decoder peeked {:02x}", byte)?,
+			(None, false) => writeln!(writer, "decoder partial {}", hex(&input.partial))?,
+			(None, true) => writeln!(writer, "decoder")?,
+		}
+
+		writeln!(writer, "line {} {}", input.is_line_ended as u8, hex(&input.line))?;
+		writeln!(writer, "number {}", input.number)?;
+
+		writeln!(writer, "stacks {}", self.stacks.len())?;
+
+		for (index, values) in &self.stacks {
+			write!(writer, "stack {}", index)?;
+
+			for value in values {
+				write!(writer, " {}", value)?;
+			}
+
+			writeln!(writer)?;
+		}
+
+		Ok(())
+	}
+
+	/// Read a state file written by [`write_to`](#method.write_to).
+	pub fn read_from<R: Read>(mut reader: R) -> io::Result<Snapshot<V>> {
+		let mut text = String::new();
+		reader.read_to_string(&mut text)?;
+
+		let mut lines = text.lines();
+
+		if lines.next() != Some(format!("col-state {}", VERSION).as_str()) {
+			return Err(invalid("not a state file, or an unsupported version"));
+		}
+
+		let position = fields(&mut lines, "position", 3)?;
+		let string = fields(&mut lines, "string", 2)?;
+		let steps = fields(&mut lines, "steps", 2)?;
+		let terminated = fields(&mut lines, "terminated", 1)?;
+		let random = fields(&mut lines, "random", 0)?;
+
+		let random = match random.as_slice() {
+			["unseeded"] => RandomState::Unseeded,
+			["default", state] => RandomState::Default(parse(state)?),
+			["replay"] => RandomState::Replay(Vec::new()),
+			["replay", bytes] => RandomState::Replay(parse_hex(bytes)?),
+			["custom"] => RandomState::Custom,
+			_ => return Err(invalid("invalid random state")),
+		};
+
+		let source = match fields(&mut lines, "input", 0)?.as_slice() {
+			["reader"] => InputSource::Reader,
+			["replay", is_ended, has_run_out, remaining @ ..] if remaining.len() <= 1 => InputSource::Replay {
+				remaining: remaining.first().map_or(Ok(Vec::new()), |bytes| parse_hex(bytes))?,
+				is_ended: parse_bool(is_ended)?,
+				has_run_out: parse_bool(has_run_out)?,
+			},
+			["pushed", is_closed, remaining @ ..] if remaining.len() <= 1 => InputSource::Pushed {
+				remaining: remaining.first().map_or(Ok(Vec::new()), |bytes| parse_hex(bytes))?,
+				is_closed: parse_bool(is_closed)?,
+			},
+			_ => return Err(invalid("invalid input source")),
+		};
+
+		// a byte is only peeked once the character before it was cut short, so never both
+		let (partial, peeked) = match fields(&mut lines, "decoder", 0)?.as_slice() {
+			[] => (Vec::new(), None),
+			["partial", bytes] if bytes.len() <= 6 => (parse_hex(bytes)?, None),
+			["peeked", byte] if byte.len() == 2 => (Vec::new(), Some(parse_hex(byte)?[0])),
+			_ => return Err(invalid("invalid decoder state")),
+		};
+
+		let line = fields(&mut lines, "line", 0)?;
+		let (is_line_ended, line) = match line.as_slice() {
+			[is_ended] => (parse_bool(is_ended)?, Vec::new()),
+			[is_ended, bytes] => (parse_bool(is_ended)?, parse_hex(bytes)?),
+			_ => return Err(invalid("invalid line")),
+		};
+
+		let number = match fields(&mut lines, "number", 0)?.as_slice() {
+			[] => String::new(),
+			[number] if number.trim_start_matches('-').bytes().all(|c| c.is_ascii_digit()) => number.to_string(),
+			_ => return Err(invalid("invalid number")),
+		};
+
+		let count: usize = parse(fields(&mut lines, "stacks", 1)?[0])?;
+		let mut stacks = Vec::with_capacity(count);
+
+		for _ in 0..count {
+			let stack = fields(&mut lines, "stack", 0)?;
+			let (index, values) = stack.split_first().ok_or_else(|| invalid("missing stack index"))?;
+
+			let values = values.iter()
+				.map(|value| parse(value))
+				.collect::<io::Result<Vec<V>>>()?;
+
+			stacks.push((parse(index)?, values));
+		}
+
+		Ok(Snapshot {
+			stacks,
+			local_column: parse(position[0])?,
+			remote_column: parse(position[1])?,
+			ip: parse(position[2])?,
+			is_string_mode: parse_bool(string[0])?,
+			string_offset: parse(string[1])?,
+			steps: parse(steps[0])?,
+			gc_count: parse(steps[1])?,
+			is_terminated: parse_bool(terminated[0])?,
+			random,
+			input: InputState { source, partial, peeked, line, is_line_ended, number },
+		})
+	}
+}
+
+/// Read the values of the next line, which has to start with `name` and have `count` values, or
+/// any number of them if `count` is zero.
+fn fields<'a>(lines: &mut Lines<'a>, name: &str, count: usize) -> io::Result<Vec<&'a str>> {
+	let line = lines.next().ok_or_else(|| invalid("unexpected end of file"))?;
+	let mut words = line.split(' ');
+
+	if words.next() != Some(name) {
+		return Err(invalid("missing line"));
+	}
+
+	let values: Vec<&str> = words.filter(|word| !word.is_empty()).collect();
+
+	if count != 0 && values.len() != count {
+		return Err(invalid("wrong number of values"));
+	}
+
+	Ok(values)
+}
+
+fn parse<T: FromStr>(value: &str) -> io::Result<T> {
+	value.parse().map_err(|_| invalid("invalid value"))
+}
+
+/// Bytes in hexadecimal, two digits each.
+fn hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex(hex: &str) -> io::Result<Vec<u8>> {
+	// an odd digit at the end has no pair
+	(0..hex.len()).step_by(2)
+		.map(|i| hex.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+		.collect::<Option<Vec<u8>>>()
+		.ok_or_else(|| invalid("invalid bytes"))
+}
+
+fn parse_bool(value: &str) -> io::Result<bool> {
+	match value {
+		"0" => Ok(false),
+		"1" => Ok(true),
+		_ => Err(invalid("invalid flag")),
+	}
+}
+
+impl<'a, P: ProgramState, T: Threading> Interpreter<'a, P, T> {
+	/// Save everything the interpreter holds, to be [restored](#method.restore) later.
+	///
+	/// See [`Snapshot`](struct.Snapshot.html) for an example.
+	pub fn snapshot(&self) -> Snapshot<P::Value> {
+		let random = match &self.random.generator {
+			Generator::Unseeded => RandomState::Unseeded,
+			Generator::Default(rng) => RandomState::Default(rng.state()),
+			Generator::Custom(_) => RandomState::Custom,
+			Generator::Replay(rng) => RandomState::Replay(rng.remaining().to_vec()),
+		};
+
+		let io = &self.io;
+
+		let source = match (&io.replay, &io.pushed) {
+			(Some(replay), _) => InputSource::Replay {
+				remaining: replay.get_ref()[replay.position() as usize..].to_vec(),
+				is_ended: io.is_replay_ended,
+				has_run_out: io.has_replay_run_out,
+			},
+			(None, Some(pushed)) => InputSource::Pushed { remaining: pushed.buffer.iter().copied().collect(), is_closed: pushed.is_closed },
+			(None, None) => InputSource::Reader,
+		};
+
+		let input = InputState {
+			source,
+			partial: io.decoder.partial.clone(),
+			peeked: io.decoder.peeked,
+			line: io.line.iter().copied().collect(),
+			is_line_ended: io.is_line_ended,
+			number: io.number.clone(),
+		};
+
+		Snapshot {
+			stacks: self.state.stacks().iter().map(|(index, values)| (*index, values.to_vec())).collect(),
+			local_column: self.local_column,
+			remote_column: self.remote_column,
+			ip: self.ip,
+			is_string_mode: self.is_string_mode,
+			string_offset: self.string_offset,
+			steps: self.steps,
+			gc_count: self.gc_count,
+			is_terminated: self.is_terminated,
+			random,
+			input,
+		}
+	}
+
+	/// Continue from a snapshot, replacing the stacks and everything else it holds.
+	///
+	/// The program doesn't have to be the one the snapshot was taken of, as long as the snapshot
+	/// is somewhere the program has an instruction.
+	pub fn restore(&mut self, snapshot: Snapshot<P::Value>) -> Result<(), Error> {
+		let column = self.program.column(snapshot.local_column)
+			.ok_or(Error::InvalidSnapshot("the local column doesn't exist"))?;
+
+		if snapshot.ip >= column.len() && !(column.is_empty() && snapshot.ip == 0) {
+			return Err(Error::InvalidSnapshot("the instruction pointer is past the end of the column"));
+		}
+
+		if snapshot.is_string_mode {
+			let in_string = column.instructions()[snapshot.ip as usize] == Instruction::StringMode
+				&& (snapshot.string_offset as usize) < column.string(snapshot.ip).len();
+
+			if !in_string {
+				return Err(Error::InvalidSnapshot("string mode isn't in a string"));
+			}
+		}
+
+		let mut state = P::new(self.program.len());
+		let mut cells = 0;
+
+		for (index, values) in snapshot.stacks {
+			state.init_stack(&index);
+			cells += values.len();
+
+			let mut stack = state.nth(index).unwrap().borrow_mut();
+
+			for value in values {
+				stack.push(value);
+			}
+		}
+
+		state.init_stack(&snapshot.remote_column);
+
+		self.state = state;
+		self.cells = cells;
+		self.local_column = snapshot.local_column;
+		self.remote_column = snapshot.remote_column;
+		self.ip = snapshot.ip;
+		self.is_string_mode = snapshot.is_string_mode;
+		self.string_offset = snapshot.string_offset;
+		self.steps = snapshot.steps;
+		self.gc_count = snapshot.gc_count.min(super::GC_STEPS - 1);
+		self.is_terminated = snapshot.is_terminated;
+		self.waiting_at = None;
+
+		match snapshot.random {
+			RandomState::Unseeded => self.random.generator = Generator::Unseeded,
+			RandomState::Default(state) => self.random.generator = Generator::Default(DefaultRng::seed_from_u64(state)),
+			RandomState::Replay(bytes) => self.random.generator = Generator::Replay(ReplayRng::new(bytes)),
+			// whatever the interpreter was given is kept
+			RandomState::Custom => {},
+		}
+
+		let input = snapshot.input;
+
+		match input.source {
+			// whatever the interpreter reads from is kept
+			InputSource::Reader => {},
+			InputSource::Replay { remaining, is_ended, has_run_out } => {
+				self.io.replay = Some(Cursor::new(remaining));
+				self.io.is_replay_ended = is_ended;
+				self.io.has_replay_run_out = has_run_out;
+			},
+			InputSource::Pushed { remaining, is_closed } => {
+				self.io.replay = None;
+				self.io.pushed = Some(PushedInput { buffer: remaining.into(), is_closed });
+			},
+		}
+
+		self.io.decoder = Decoder { partial: input.partial, peeked: input.peeked };
+		self.io.line = input.line.into();
+		self.io.is_line_ended = input.is_line_ended;
+		self.io.number = input.number;
+
+		Ok(())
+	}
+}
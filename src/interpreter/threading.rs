@@ -0,0 +1,58 @@
+//! Whether an interpreter can be moved to another thread.
+
+use std::cell::Ref;
+use std::io::{Read, Write};
+
+use rand::RngCore;
+
+use super::{IoHandler, Observer, ProgramState};
+
+/// What an interpreter can hold, which decides whether it can be moved to another thread.
+///
+/// An interpreter which borrows its input and output is [`Local`](struct.Local.html), and can hold
+/// anything. One which [owns them](struct.Interpreter.html#method.from_owned) is
+/// [`Sendable`](struct.Sendable.html), and only holds what is `Send`, so it is `Send` itself.
+pub trait Threading: Default {
+	/// Where a program reads its input from
+	type Reader<'a>: Read + ?Sized;
+	/// Where a program writes its output to
+	type Writer<'a>: Write + ?Sized;
+	/// Callback function for after each step
+	type StepCallback<'a, V>: Fn(Vec<(u32, Ref<Vec<V>>)>) + ?Sized;
+	/// Where random values come from
+	type Rng<'a>: RngCore + ?Sized;
+	/// What watches the instructions being executed
+	type Observer<'a, V>: Observer<V> + ?Sized;
+	/// What reads and prints in place of the reader and writer
+	type IoHandler<'a>: IoHandler + ?Sized;
+	/// Condition for a breakpoint to stop
+	type Condition<'a, P: ProgramState>: Fn(&[P::Value], &P) -> bool + ?Sized;
+}
+
+/// An interpreter which stays on the thread it was created on, the default.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Local;
+
+impl Threading for Local {
+	type Reader<'a> = dyn Read + 'a;
+	type Writer<'a> = dyn Write + 'a;
+	type StepCallback<'a, V> = dyn Fn(Vec<(u32, Ref<Vec<V>>)>) + 'a;
+	type Rng<'a> = dyn RngCore + 'a;
+	type Observer<'a, V> = dyn Observer<V> + 'a;
+	type IoHandler<'a> = dyn IoHandler + 'a;
+	type Condition<'a, P: ProgramState> = dyn Fn(&[P::Value], &P) -> bool + 'a;
+}
+
+/// An interpreter which can be moved to another thread.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sendable;
+
+impl Threading for Sendable {
+	type Reader<'a> = dyn Read + Send + 'a;
+	type Writer<'a> = dyn Write + Send + 'a;
+	type StepCallback<'a, V> = dyn Fn(Vec<(u32, Ref<Vec<V>>)>) + Send + 'a;
+	type Rng<'a> = dyn RngCore + Send + 'a;
+	type Observer<'a, V> = dyn Observer<V> + Send + 'a;
+	type IoHandler<'a> = dyn IoHandler + Send + 'a;
+	type Condition<'a, P: ProgramState> = dyn Fn(&[P::Value], &P) -> bool + Send + 'a;
+}
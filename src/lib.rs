@@ -7,9 +7,13 @@
 //! To interpret col in your own program, see the [interpreter](interpreter)
 //! documentation.
 
+pub mod error;
 pub mod parser;
 pub mod interpreter;
 pub mod program;
+pub mod value;
+
+pub use error::Error;
 
 #[cfg(test)]
 mod test;
\ No newline at end of file
@@ -0,0 +1,140 @@
+//! Static checks for common mistakes in col programs.
+
+use std::fmt;
+
+use super::{Instruction, Program};
+
+/// The kind of problem found by [`check`](fn.check.html).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DiagnosticKind {
+	/// A `[` without a matching `]`, which jumps back to the start of the column instead.
+	UnmatchedLeftBracket,
+	/// A `]` without a matching `[`, which jumps back to the start of the column instead.
+	UnmatchedRightBracket,
+	/// A `"` without a closing `"`, whose string wraps around the end of the column.
+	///
+	/// A column with a single `"` isn't reported, as pushing the rest of the line as a string is
+	/// how a quine is written.
+	UnterminatedString,
+	/// A column without a single executable instruction, which never advances once entered.
+	EmptyColumn,
+	/// The program has no `@` anywhere, so it can never terminate.
+	MissingTerminator,
+}
+
+/// A range of characters within a single line of source code.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+	/// Index of the line, which is also the index of the column
+	pub line: u32,
+	/// Character offset of the start of the span
+	pub start: u32,
+	/// Character offset of the end of the span (exclusive)
+	pub end: u32,
+}
+
+/// A problem found in a col program.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+	pub kind: DiagnosticKind,
+	/// Where the problem is, if it can be pinned to one place
+	pub span: Option<Span>,
+}
+
+/// Check a program for mistakes which are technically valid col, but almost never intended.
+///
+/// # Example
+///
+/// ```
+/// use col::parser::{self, DiagnosticKind};
+///
+/// let diagnostics = parser::check(&parser::parse("1[#@"));
+///
+/// assert_eq!(diagnostics[0].kind, DiagnosticKind::UnmatchedLeftBracket);
+/// assert_eq!(diagnostics[0].span.unwrap().start, 1);
+/// ```
+pub fn check(program: &Program) -> Vec<Diagnostic> {
+	let mut diagnostics = Vec::new();
+	let mut has_terminator = false;
+
+	for (line, column) in program.columns().iter().enumerate() {
+		let line = line as u32;
+		let span = |ip: u32| {
+			let start = column.offset(ip)?;
+			Some(Span { line, start, end: start + 1 })
+		};
+
+		if column.is_empty() {
+			let end = column.source().chars().count() as u32;
+
+			diagnostics.push(Diagnostic {
+				kind: DiagnosticKind::EmptyColumn,
+				span: Some(Span { line, start: 0, end }),
+			});
+		}
+
+		let mut open = Vec::new();
+		let mut quote = None;
+		let code = column.code();
+
+		for (ip, instr) in column.instructions().iter().enumerate() {
+			let ip = ip as u32;
+
+			match instr {
+				Instruction::LeftBracket => open.push(ip),
+				Instruction::RightBracket => match open.pop() {
+					Some(_) => {},
+					None => diagnostics.push(Diagnostic { kind: DiagnosticKind::UnmatchedRightBracket, span: span(ip) }),
+				},
+				Instruction::StringMode => {
+					quote = match quote {
+						Some(_) => None,
+						None => Some(ip),
+					};
+				},
+				// a `@` inside a string is only pushed as a character
+				Instruction::Terminate if code[ip as usize] => has_terminator = true,
+				_ => {},
+			}
+		}
+
+		for ip in open {
+			diagnostics.push(Diagnostic { kind: DiagnosticKind::UnmatchedLeftBracket, span: span(ip) });
+		}
+
+		let quotes = column.instructions().iter().filter(|instr| **instr == Instruction::StringMode).count();
+
+		if let Some(ip) = quote.filter(|_| quotes > 1) {
+			diagnostics.push(Diagnostic { kind: DiagnosticKind::UnterminatedString, span: span(ip) });
+		}
+	}
+
+	diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| (span.line, span.start)));
+
+	if !has_terminator {
+		diagnostics.push(Diagnostic { kind: DiagnosticKind::MissingTerminator, span: None });
+	}
+
+	diagnostics
+}
+
+impl fmt::Display for DiagnosticKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			DiagnosticKind::UnmatchedLeftBracket => "unmatched `[`, execution will jump to the start of the column",
+			DiagnosticKind::UnmatchedRightBracket => "unmatched `]`, execution will jump to the start of the column",
+			DiagnosticKind::UnterminatedString => "unterminated `\"`, the string wraps around the end of the column",
+			DiagnosticKind::EmptyColumn => "column has no instructions and will never advance once entered",
+			DiagnosticKind::MissingTerminator => "program has no `@` and can never terminate",
+		})
+	}
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.span {
+			Some(span) => write!(f, "{}:{}: {}", span.line + 1, span.start + 1, self.kind),
+			None => write!(f, "{}", self.kind),
+		}
+	}
+}
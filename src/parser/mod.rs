@@ -9,6 +9,23 @@
 //!
 //! assert_eq!(instr, Some(Instruction::Terminate));
 //! ```
+//!
+//! Whole programs are compiled into a reusable [`Program`](struct.Program.html).
+//!
+//! ```
+//! use col::parser::{self, Instruction};
+//!
+//! let program = parser::parse("8[1-:#A$]@");
+//!
+//! assert_eq!(program.len(), 1);
+//! assert_eq!(program.column(0).unwrap().instruction(1), Some(Instruction::LeftBracket));
+//! ```
+
+mod check;
+mod program;
+
+pub use check::{check, Diagnostic, DiagnosticKind, Span};
+pub use program::{parse, Column, Program};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum Instruction {
@@ -124,4 +141,44 @@ impl Instruction {
 			_ => return None,
 		})
 	}
+
+	/// Converts a col instruction back to the character it is written as
+	pub fn to_char(&self) -> char {
+		match self {
+			Instruction::PushLeftIndex => '<',
+			Instruction::PushRightIndex => '>',
+			Instruction::PushCurrentIndex => '.',
+			Instruction::SetLocalColumn => ';',
+			Instruction::SetRemoteStack => '~',
+			Instruction::MoveToRemote => '^',
+			Instruction::MoveToLocal => 'v',
+			Instruction::SwapTop => '\\',
+			Instruction::DuplicateTop => ':',
+			Instruction::Discard => 'x',
+			Instruction::Clear => 'c',
+			Instruction::SwapStacks => 's',
+			Instruction::Reverse => 'r',
+			Instruction::Value(value) => std::char::from_digit(*value, 16).unwrap_or('?').to_ascii_uppercase(),
+			Instruction::LeftBracket => '[',
+			Instruction::RightBracket => ']',
+			Instruction::Add => '+',
+			Instruction::Subtract => '-',
+			Instruction::Multiply => '*',
+			Instruction::Divide => '/',
+			Instruction::Modulo => '%',
+			Instruction::Equals => '=',
+			Instruction::GreaterThan => '`',
+			Instruction::BitwiseNand => ',',
+			Instruction::LogicalAnd => '&',
+			Instruction::LogicalOr => '|',
+			Instruction::LogicalNot => '!',
+			Instruction::Random => '?',
+			Instruction::StringMode => '"',
+			Instruction::Input => '_',
+			Instruction::PrintChar => '$',
+			Instruction::PrintNumber => '#',
+			Instruction::PrintAll => 'p',
+			Instruction::Terminate => '@',
+		}
+	}
 }
\ No newline at end of file
@@ -0,0 +1,205 @@
+//! Compiled representation of a col program.
+
+use super::Instruction;
+
+/// A parsed col program, which can be executed any number of times by an interpreter.
+///
+/// Every line of the source becomes a [`Column`](struct.Column.html) holding only its executable
+/// instructions, with the targets of brackets and string mode resolved ahead of time.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Program {
+	columns: Vec<Column>,
+}
+
+/// A single compiled column (line) of a col program.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Column {
+	/// The original line of source code
+	source: String,
+	/// The executable instructions, in source order
+	instructions: Vec<Instruction>,
+	/// The character offset of each instruction within the source line
+	offsets: Vec<u32>,
+	/// The instruction to continue at after a bracket jump or a string mode run
+	jumps: Vec<u32>,
+	/// The characters pushed by each string mode instruction
+	strings: Vec<Vec<char>>,
+}
+
+/// Parse col source code into a `Program`.
+///
+/// Lines map to columns, and characters which are not instructions are dropped (but still pushed
+/// as values when they are part of a string).
+pub fn parse(source: &str) -> Program {
+	Program {
+		columns: source.lines().map(Column::parse).collect(),
+	}
+}
+
+impl Program {
+	/// Number of program-defined columns.
+	pub fn len(&self) -> u32 {
+		self.columns.len() as u32
+	}
+
+	/// Whether or not the program has any columns at all.
+	pub fn is_empty(&self) -> bool {
+		self.columns.is_empty()
+	}
+
+	/// Get the column at a given index.
+	pub fn column(&self, index: u32) -> Option<&Column> {
+		self.columns.get(index as usize)
+	}
+
+	/// All of the columns of the program, in order.
+	pub fn columns(&self) -> &[Column] {
+		&self.columns
+	}
+}
+
+impl Column {
+	fn parse(line: &str) -> Column {
+		let chars: Vec<char> = line.chars().collect();
+
+		let mut instructions = Vec::new();
+		let mut offsets = Vec::new();
+
+		for (offset, c) in chars.iter().enumerate() {
+			if let Some(instr) = Instruction::from_char(c) {
+				instructions.push(instr);
+				offsets.push(offset as u32);
+			}
+		}
+
+		let len = instructions.len();
+		let next = |index: usize| ((index + 1) % len) as u32;
+
+		// unmatched brackets return to the start of the column
+		let mut jumps = vec![0; len];
+		let mut strings = vec![Vec::new(); len];
+
+		// pair up the brackets
+		let mut open = Vec::new();
+
+		for (index, instr) in instructions.iter().enumerate() {
+			match instr {
+				Instruction::LeftBracket => open.push(index),
+				Instruction::RightBracket => {
+					if let Some(left) = open.pop() {
+						jumps[left] = next(index);
+						jumps[index] = next(left);
+					}
+				},
+				_ => {},
+			}
+		}
+
+		// a string runs until the next quote, wrapping around the end of the line if needed
+		let quotes: Vec<usize> = (0..len)
+			.filter(|index| instructions[*index] == Instruction::StringMode)
+			.collect();
+
+		for (n, quote) in quotes.iter().enumerate() {
+			let closing = quotes[(n + 1) % quotes.len()];
+
+			let start = offsets[*quote] as usize + 1;
+			let end = offsets[closing] as usize;
+
+			strings[*quote] = if start <= end {
+				chars[start..end].to_vec()
+			} else {
+				chars[start..].iter().chain(&chars[..end]).copied().collect()
+			};
+
+			jumps[*quote] = next(closing);
+		}
+
+		Column {
+			source: line.to_owned(),
+			instructions,
+			offsets,
+			jumps,
+			strings,
+		}
+	}
+
+	/// The original line of source code.
+	pub fn source(&self) -> &str {
+		&self.source
+	}
+
+	/// The executable instructions of the column.
+	pub fn instructions(&self) -> &[Instruction] {
+		&self.instructions
+	}
+
+	/// Number of executable instructions.
+	pub fn len(&self) -> u32 {
+		self.instructions.len() as u32
+	}
+
+	/// Whether or not the column has no executable instructions.
+	pub fn is_empty(&self) -> bool {
+		self.instructions.is_empty()
+	}
+
+	/// Get the instruction at a given instruction pointer.
+	pub fn instruction(&self, ip: u32) -> Option<Instruction> {
+		self.instructions.get(ip as usize).copied()
+	}
+
+	/// The character offset of an instruction within the source line, or `None` if there is no
+	/// instruction at `ip`, as in an empty column.
+	pub fn offset(&self, ip: u32) -> Option<u32> {
+		self.offsets.get(ip as usize).copied()
+	}
+
+	/// Whitespace as wide as the source line up to a character offset, keeping its tabs, so
+	/// whatever is printed after it lines up with the character there.
+	pub fn indent(&self, offset: u32) -> String {
+		self.source.chars()
+			.take(offset as usize)
+			.map(|c| if c == '\t' { '\t' } else { ' ' })
+			.collect()
+	}
+
+	/// Where execution continues after the bracket or string mode instruction at `ip` jumps.
+	pub fn jump(&self, ip: u32) -> u32 {
+		self.jumps[ip as usize]
+	}
+
+	/// The characters pushed by the string mode instruction at `ip`.
+	pub fn string(&self, ip: u32) -> &[char] {
+		&self.strings[ip as usize]
+	}
+
+	/// Which instructions are code, reading the column from its start, rather than the characters
+	/// of a string up to and including its closing quote.
+	///
+	/// Only strings closed before the end of the line are left out. One which wraps around the end
+	/// is also run as code, as is everything after a lone quote.
+	pub(crate) fn code(&self) -> Vec<bool> {
+		let len = self.len();
+		let mut code = vec![true; len as usize];
+		let mut ip = 0;
+
+		while ip < len {
+			if self.instructions[ip as usize] != Instruction::StringMode {
+				ip += 1;
+				continue;
+			}
+
+			let end = self.jump(ip);
+
+			if end <= ip + 1 {
+				break;
+			}
+
+			code[ip as usize + 1..end as usize].iter_mut().for_each(|is_code| *is_code = false);
+			ip = end;
+		}
+
+		code
+	}
+}
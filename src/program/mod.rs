@@ -2,18 +2,26 @@
 
 use std::cell::{RefCell, Ref};
 
+use crate::value::Value;
+
 mod simple;
 
 pub use simple::SimpleProgramState;
 
 pub trait ProgramState: Default {
+	/// The type of the values held in the stacks.
+	type Value: Value;
+
 	/// Create a new `ProgramState` with an initial number of empty `VecStack`s
 	fn new(initial_count: u32) -> Self;
 
 	/// Get the nth program stack as a RefCell to be borrowed as immutable or mutable.
 	///
 	/// This does NOT insert a new stack if one does not exist (see `insert_stack).
-	fn nth(&self, index: u32) -> Option<&RefCell<VecStack>>;
+	fn nth(&self, index: u32) -> Option<&RefCell<VecStack<Self::Value>>>;
+
+	/// The number of stacks which currently exist.
+	fn stack_count(&self) -> u32;
 
 	/// Invoke basic garbage collection. The implementation will vary.
 	fn discard_unused(&mut self, program_defined: &u32, remote_index: &u32);
@@ -22,35 +30,39 @@ pub trait ProgramState: Default {
 	/// If one already exists at the index, then nothing should happen.
 	fn init_stack(&mut self, index: &u32);
 
-	/// Immutable view of of the program memory.
-	fn stacks(&self) -> Vec<(u32, Ref<Vec<u32>>)>;
+	/// Immutable view of of the program memory, in order of index.
+	fn stacks(&self) -> Vec<(u32, Ref<'_, Vec<Self::Value>>)>;
 }
 
 #[derive(Clone, Default, Debug)]
-pub struct VecStack {
-	stack: Vec<u32>,
+pub struct VecStack<V = u32> {
+	stack: Vec<V>,
 }
 
 // this should probably be documented but it's laughably self-documenting so...
-impl VecStack {
-	pub fn values(&self) -> &Vec<u32> {
+impl<V: Value> VecStack<V> {
+	pub fn values(&self) -> &Vec<V> {
 		&self.stack
 	}
 
-	pub fn push(&mut self, value: u32) {
+	pub fn push(&mut self, value: V) {
 		self.stack.push(value);
 	}
 
-	pub fn pop(&mut self) -> u32 {
+	pub fn pop(&mut self) -> V {
 		self.stack.pop().unwrap_or_default()
 	}
 
-	pub fn pop2(&mut self) -> (u32, u32) {
+	pub fn try_pop(&mut self) -> Option<V> {
+		self.stack.pop()
+	}
+
+	pub fn pop2(&mut self) -> (V, V) {
 		(self.pop(), self.pop())
 	}
 
-	pub fn peek(&self) -> u32 {
-		*self.stack.last().unwrap_or(&0)
+	pub fn peek(&self) -> V {
+		self.stack.last().cloned().unwrap_or_default()
 	}
 
 	pub fn clear(&mut self) {
@@ -61,11 +73,15 @@ impl VecStack {
 		self.stack.reverse()
 	}
 
+	pub fn len(&self) -> usize {
+		self.stack.len()
+	}
+
 	pub fn is_empty(&self) -> bool {
 		self.stack.is_empty()
 	}
 
-	pub fn swap(a: &mut VecStack, b: &mut VecStack) {
+	pub fn swap(a: &mut VecStack<V>, b: &mut VecStack<V>) {
 		std::mem::swap(a, b);
 	}
 }
\ No newline at end of file
@@ -6,11 +6,13 @@ use std::cell::{RefCell, Ref};
 use super::*;
 
 #[derive(Debug, Default)]
-pub struct SimpleProgramState {
-	stacks: HashMap<u32, RefCell<VecStack>>,
+pub struct SimpleProgramState<V = u32> {
+	stacks: HashMap<u32, RefCell<VecStack<V>>>,
 }
 
-impl ProgramState for SimpleProgramState {
+impl<V: Value> ProgramState for SimpleProgramState<V> {
+	type Value = V;
+
 	fn new(initial_count: u32) -> Self {
 		let mut stacks = HashMap::new();
 
@@ -21,10 +23,14 @@ impl ProgramState for SimpleProgramState {
 		SimpleProgramState { stacks }
 	}
 
-	fn nth(&self, index: u32) -> Option<&RefCell<VecStack>> {
+	fn nth(&self, index: u32) -> Option<&RefCell<VecStack<V>>> {
 		self.stacks.get(&index)
 	}
 
+	fn stack_count(&self) -> u32 {
+		self.stacks.len() as u32
+	}
+
 	fn discard_unused(&mut self, program_defined: &u32, remote_index: &u32) {
 		// remove empty stacks that aren't being used by the program or the remote stack.
 		// we could also use a queuing system that delays the removal, but I don't think allocating
@@ -34,7 +40,7 @@ impl ProgramState for SimpleProgramState {
 				return !stack.borrow().is_empty(); // remove only if empty
 			}
 
-			return true; // if it's part of the program defined stacks, keep it!
+			true // if it's part of the program defined stacks, keep it!
 		});
 	}
 
@@ -46,11 +52,15 @@ impl ProgramState for SimpleProgramState {
 		self.stacks.insert(*index, RefCell::new(VecStack::default()));
 	}
 
-	fn stacks(&self) -> Vec<(u32, Ref<Vec<u32>>)> {
-		self.stacks.iter().map(|(index, stack)| {
+	fn stacks(&self) -> Vec<(u32, Ref<'_, Vec<V>>)> {
+		let mut stacks: Vec<_> = self.stacks.iter().map(|(index, stack)| {
 			let stack = Ref::map(stack.borrow(), |s| s.values());
 
 			(*index, stack)
-		}).collect()
+		}).collect();
+
+		// the order of a hash map changes from run to run
+		stacks.sort_by_key(|(index, _)| *index);
+		stacks
 	}
 }
\ No newline at end of file
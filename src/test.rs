@@ -1,22 +1,44 @@
 use std::io::Read;
 
-use crate::interpreter::Interpreter;
+use crate::interpreter::{Backend, CancellationHandle, ExecutionStatus, Interpreter, Limits, OutputEvent, OverflowPolicy, StrictMode};
+use crate::parser;
 use crate::program::SimpleProgramState;
+use crate::value::Value;
 
-fn get_output_with_input<R: Read>(source: &str, reader: &mut R) -> String {
-	let mut output = Vec::new();
-	Interpreter::<SimpleProgramState>::new(source, Some(reader), Some(&mut output))
-		.run()
-		.unwrap();
-	return String::from_utf8_lossy(output.as_slice()).into_owned();
+type Configure<V> = dyn Fn(Interpreter<SimpleProgramState<V>>) -> Interpreter<SimpleProgramState<V>>;
+
+/// Run a configured program with every backend, making sure they all agree on the result.
+fn try_all_backends<V: Value>(source: &str, input: Option<&str>, configure: &Configure<V>) -> Result<String, String> {
+	let results: Vec<Result<String, String>> = [Backend::Direct, Backend::Bytecode].iter().map(|backend| {
+		let mut output = Vec::new();
+		let mut reader = input.map(str::as_bytes);
+
+		let interpreter = Interpreter::<SimpleProgramState<V>>::new(source, reader.as_mut().map(|r| r as &mut dyn Read), Some(&mut output))
+			.with_backend(*backend);
+
+		configure(interpreter).run().map_err(|e| e.to_string())?;
+		Ok(String::from_utf8_lossy(output.as_slice()).into_owned())
+	}).collect();
+
+	assert_eq!(results[0], results[1], "backends disagree on {:?}", source);
+	results[0].clone()
+}
+
+/// Run a program with every backend, making sure they all agree on the output.
+fn run_all_backends<V: Value>(source: &str, input: Option<&str>) -> String {
+	try_all_backends::<V>(source, input, &|interpreter| interpreter).unwrap()
+}
+
+fn get_output_with_input(source: &str, input: &str) -> String {
+	run_all_backends::<u32>(source, Some(input))
 }
 
 fn get_output(source: &str) -> String {
-	let mut output = Vec::new();
-	Interpreter::<SimpleProgramState>::new(source, None, Some(&mut output))
-		.run()
-		.unwrap();
-	return String::from_utf8_lossy(output.as_slice()).into_owned()
+	run_all_backends::<u32>(source, None)
+}
+
+fn get_output_as<V: Value>(source: &str) -> String {
+	run_all_backends::<V>(source, None)
 }
 
 #[test]
@@ -38,6 +60,567 @@ fn test_math() {
 	assert_eq!(get_output("1-  5+#@"), "4");
 }
 
+#[test]
+fn test_overflow_policy() {
+	let saturate = |source| try_all_backends::<u32>(source, None, &|i| i.with_overflow_policy(OverflowPolicy::Saturate));
+	let trap = |source| try_all_backends::<u32>(source, None, &|i| i.with_overflow_policy(OverflowPolicy::Trap));
+
+	assert_eq!(saturate("1-# 0F-# F1+F1+*:*:*:*# 50/# @"), Ok("0042949672950".to_string()));
+	assert_eq!(trap("5 3-# 1-# @"), Err("arithmetic overflow (0 - 1) at column 0, instruction 5".to_string()));
+	assert_eq!(trap("1 1 -# @"), Ok("0".to_string()));
+	assert_eq!(try_all_backends::<u8>("FF*2*# @", None, &|i| i.with_overflow_policy(OverflowPolicy::Trap)),
+		Err("arithmetic overflow (225 * 2) at column 0, instruction 4".to_string()));
+
+	// the remainder can overflow too, like the division
+	for source in &["01-/@", "01-%@"] {
+		let mut interpreter = Interpreter::<SimpleProgramState<i64>>::new(source, None, None)
+			.with_overflow_policy(OverflowPolicy::Trap);
+
+		interpreter.set_stack(0, vec![i64::MIN]);
+		assert!(interpreter.run().unwrap_err().to_string().starts_with("arithmetic overflow"));
+	}
+}
+
+#[test]
+fn test_strict_mode() {
+	let strict = |source, input| try_all_backends::<u32>(source, input, &|i| i.with_strict_mode(StrictMode::all()));
+	let only = |source, mode| try_all_backends::<u32>(source, None, &move |i| i.with_strict_mode(mode));
+
+	assert_eq!(strict("01-# 1~5:^v# \"ab\"$$ @", None), Ok("42949672955ba".to_string()));
+	assert_eq!(strict("1#$@", None), Err("popped an empty local stack at column 0, instruction 2".to_string()));
+	assert_eq!(strict("5~3^v5~v@", None), Err("popped an empty remote stack at column 0, instruction 7".to_string()));
+	assert_eq!(strict("_#_#@", Some("a")), Err("read past the end of the input at column 0, instruction 2".to_string()));
+	assert_eq!(strict("50/#@", None), Err("division by zero at column 0, instruction 2".to_string()));
+	assert_eq!(strict("5 0%#@", None), Err("division by zero at column 0, instruction 2".to_string()));
+	assert_eq!(strict("5:^@", None), Err("no remote stack has been set at column 0, instruction 2".to_string()));
+	assert_eq!(strict("s@", None), Err("no remote stack has been set at column 0, instruction 0".to_string()));
+
+	// each condition is separate
+	let division = StrictMode { division_by_zero: true, ..StrictMode::default() };
+	assert_eq!(only("$ 5^ 1!# 50/#@", division), Err("division by zero at column 0, instruction 8".to_string()));
+	assert_eq!(only("$ 5^ 1!# 50/#@", StrictMode { division_by_zero: false, ..StrictMode::all() }),
+		Err("popped an empty local stack at column 0, instruction 0".to_string()));
+}
+
+#[test]
+fn test_limits() {
+	let limited = |source, limits| try_all_backends::<u32>(source, None, &move |i| i.with_limits(limits));
+	let steps = |max| Limits { max_steps: Some(max), ..Limits::default() };
+
+	assert_eq!(limited("12345#@", steps(7)), Ok("5".to_string()));
+	assert_eq!(limited("12345#@", steps(6)), Err("exceeded the limit of 6 steps at column 0, instruction 6".to_string()));
+	assert_eq!(limited("1[]@", steps(1001)), Err("exceeded the limit of 1001 steps at column 0, instruction 2".to_string()));
+
+	let depth = Limits { max_stack_depth: Some(100), ..Limits::default() };
+	assert_eq!(limited("1[:]@", depth), Err("exceeded the limit of 100 values in a stack at column 0, instruction 2".to_string()));
+
+	let cells = Limits { max_cells: Some(5), ..Limits::default() };
+	assert_eq!(limited("12~345^^x@", cells), Ok("".to_string()));
+	assert_eq!(limited("\"abcdef\"@", cells), Err("exceeded the limit of 5 values in total at column 0, instruction 0".to_string()));
+
+	let columns = Limits { max_columns: Some(10), ..Limits::default() };
+	assert_eq!(limited("1[:1+:~1^]@", columns), Err("exceeded the limit of 10 columns at column 0, instruction 6".to_string()));
+	assert_eq!(limited("1\n@\n\n", Limits { max_columns: Some(1), ..Limits::default() }),
+		Err("exceeded the limit of 1 columns at column 0, instruction 0".to_string()));
+
+	// empty stacks are collected before giving up
+	assert_eq!(limited("1[1+:~]@", Limits { max_steps: Some(100_000), ..columns }),
+		Err("exceeded the limit of 100000 steps at column 0, instruction 5".to_string()));
+
+	// a program stuck in an empty column fails there, where there is no character to point at
+	let program = parser::parse("1;\n   \n");
+	let error = Interpreter::<SimpleProgramState>::from_program(&program, None, None)
+		.with_limits(steps(10))
+		.run()
+		.unwrap_err();
+
+	let location = error.location().unwrap();
+	assert_eq!((location.column, location.ip), (1, 0));
+	assert_eq!(program.column(location.column).unwrap().offset(location.ip), None);
+}
+
+#[test]
+fn test_cancellation() {
+	use std::io::{self, Write};
+	use std::sync::mpsc::{self, Sender};
+	use std::time::Instant;
+	use crate::error::Error;
+	use crate::interpreter::Sendable;
+
+	/// Tells the test the program is running once it prints something.
+	struct Running(Sender<()>);
+
+	impl Write for Running {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			let _ = self.0.send(());
+			Ok(buf.len())
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	let handle = CancellationHandle::new();
+	let cancellation = handle.clone();
+	handle.cancel();
+
+	assert_eq!(try_all_backends::<u32>("1[]@", None, &move |i| i.with_cancellation(cancellation.clone())),
+		Err("cancelled after 0 steps at column 0, instruction 0".to_string()));
+	assert_eq!(try_all_backends::<u32>("1[]@", None, &|i| i.with_deadline(Instant::now())),
+		Err("timed out after 0 steps at column 0, instruction 0".to_string()));
+
+	for backend in &[Backend::Direct, Backend::Bytecode] {
+		let backend = *backend;
+		let handle = CancellationHandle::new();
+		let cancellation = handle.clone();
+		let (sender, receiver) = mpsc::channel();
+
+		let running = std::thread::spawn(move || {
+			Interpreter::<SimpleProgramState, Sendable>::from_owned(parser::parse("1#1[]@"), None, Some(Box::new(Running(sender))))
+				.with_backend(backend)
+				.with_output_buffer(0)
+				.with_cancellation(cancellation)
+				.run()
+		});
+
+		// only cancel once the program has printed, so it has executed some steps
+		receiver.recv().unwrap();
+		handle.cancel();
+
+		match running.join().unwrap() {
+			Err(Error::Cancelled { steps, .. }) => assert!(steps > 0),
+			other => panic!("expected the program to be cancelled, got {:?}", other),
+		}
+	}
+}
+
+#[test]
+fn test_random() {
+	use std::time::Instant;
+	use rand::rngs::mock::StepRng;
+
+	let seeded = |seed| try_all_backends::<u32>("?#A$ ?#A$ ?#@", None, &move |i| i.with_seed(seed)).unwrap();
+
+	assert_eq!(seeded(42), seeded(42));
+	assert_ne!(seeded(42), seeded(43));
+	assert_eq!(try_all_backends::<u32>("?#A$ ?#A$ ?#@", None, &|i| i.with_deterministic(true)), Ok(seeded(0)));
+	assert_eq!(try_all_backends::<u8>("???###@", None, &|i| i.with_rng(StepRng::new(5, 1))), Ok("765".to_string()));
+
+	// the deadline still stops a program which hangs, though when depends on the machine
+	assert_eq!(try_all_backends::<u32>("1[]@", None, &|i| i.with_deadline(Instant::now()).with_deterministic(true)),
+		Err("timed out after 0 steps at column 0, instruction 0".to_string()));
+}
+
+#[test]
+fn test_replay() {
+	use crate::interpreter::{InputMode, Recording};
+	use crate::value::BigInt;
+
+	fn record<V: Value>(source: &str, input: &str) -> (String, Recording) {
+		let mut output = Vec::new();
+		let mut reader = input.as_bytes();
+		let mut interpreter = Interpreter::<SimpleProgramState<V>>::new(source, Some(&mut reader), Some(&mut output))
+			.with_recording(true);
+
+		interpreter.run().unwrap();
+		let recording = interpreter.recording().unwrap();
+		drop(interpreter);
+
+		(String::from_utf8(output).unwrap(), recording)
+	}
+
+	let source = "__?# ?# $$ _#@";
+	let (output, recording) = record::<u32>(source, "abc");
+
+	assert_eq!(recording.input, b"abc");
+	assert_eq!(recording.random.len(), 8);
+	assert_eq!(recording.options, "u32 Utf8 Char");
+
+	let replay = recording.clone();
+	assert_eq!(try_all_backends::<u32>(source, Some("xyz"), &move |i| i.with_replay(replay.clone())), Ok(output));
+
+	// the file format keeps every byte
+	let (output, recording) = record::<BigInt>("_?#?#$@", "\n");
+	assert!(recording.options.starts_with("bigint "));
+	let mut file = Vec::new();
+	recording.write_to(&mut file).unwrap();
+	assert_eq!(Recording::read_from(file.as_slice()).unwrap(), recording);
+	assert_eq!(try_all_backends::<BigInt>("_?#?#$@", None, &move |i| i.with_replay(recording.clone())), Ok(output));
+
+	assert!(Recording::read_from(&b"col-replay 2\nprogram 0\noptions u32\ninput-ended 0\ninput 5\nab\n"[..]).is_err());
+	assert!(Recording::read_from(&b"col-replay 1\ninput 0\n\nrandom 0\n\n"[..]).is_err());
+
+	// a replay has to fit the program and options it was recorded with
+	let (_, recording) = record::<u32>(source, "abc");
+	let replay = |source, configure: fn(Interpreter<SimpleProgramState>) -> Interpreter<SimpleProgramState>| {
+		let recording = recording.clone();
+		try_all_backends::<u32>(source, None, &move |i| configure(i.with_replay(recording.clone())))
+	};
+
+	assert!(replay("__?# ?# $$ _#@ ", |i| i).unwrap_err().contains("another program"));
+	assert!(replay(source, |i| i.with_input_mode(InputMode::Line)).unwrap_err().contains("another cell type"));
+	assert!(replay(source, |i| i.with_seed(1)).unwrap_err().contains("random number generator"));
+	assert!(try_all_backends::<u8>(source, None, &move |i| i.with_replay(recording.clone())).unwrap_err().contains("another cell type"));
+
+	// running out of the recording isn't the same as the input ending
+	let (_, recording) = record::<u32>("_?@", "");
+	assert!(recording.is_input_ended);
+
+	let mut short = recording.clone();
+	short.is_input_ended = false;
+	assert_eq!(try_all_backends::<u32>("_?@", None, &move |i| i.with_replay(short.clone())),
+		Err("the recording ran out at column 0, instruction 0".to_string()));
+
+	let mut short = recording.clone();
+	short.random.clear();
+	assert_eq!(try_all_backends::<u32>("_?@", None, &move |i| i.with_replay(short.clone())),
+		Err("the recording ran out at column 0, instruction 1".to_string()));
+}
+
+#[test]
+fn test_snapshot() {
+	use crate::interpreter::{InputMode, Snapshot};
+
+	trait RunAndTakeOutput {
+		fn run_and_take_output(self) -> Vec<OutputEvent>;
+	}
+
+	impl RunAndTakeOutput for Interpreter<'_, SimpleProgramState> {
+		fn run_and_take_output(mut self) -> Vec<OutputEvent> {
+			self.run().unwrap();
+			self.take_output()
+		}
+	}
+
+	let source = "5~\"hey\"^^?#A$ 1;\nv$v$ ?9%1+[1-:#]@";
+
+	for backend in &[Backend::Direct, Backend::Bytecode] {
+		let mut expected = Vec::new();
+		let mut interpreter = Interpreter::<SimpleProgramState>::new(source, None, Some(&mut expected))
+			.with_backend(*backend)
+			.with_seed(7);
+
+		interpreter.run().unwrap();
+		let steps = interpreter.steps();
+		drop(interpreter);
+
+		// stopping anywhere and continuing from a saved state gives the same output
+		for stop in 0..steps {
+			let mut output = Vec::new();
+			let mut interpreter = Interpreter::<SimpleProgramState>::new(source, None, Some(&mut output))
+				.with_backend(*backend)
+				.with_seed(7);
+
+			interpreter.run_for(stop);
+			let snapshot = interpreter.snapshot();
+			drop(interpreter);
+
+			let mut file = Vec::new();
+			snapshot.write_to(&mut file).unwrap();
+			assert_eq!(Snapshot::read_from(file.as_slice()).unwrap(), snapshot);
+
+			let mut restored = Interpreter::<SimpleProgramState>::new(source, None, Some(&mut output))
+				.with_backend(*backend);
+
+			restored.restore(snapshot).unwrap();
+			restored.run().unwrap();
+			assert_eq!(restored.steps(), steps);
+			drop(restored);
+
+			assert_eq!(output, expected, "stopped after {} steps", stop);
+		}
+	}
+
+	// the snapshot has to be somewhere in the program
+	let mut interpreter = Interpreter::<SimpleProgramState>::new("2;\n\n\"ab\"@", None, None);
+	interpreter.run_for(4);
+	let snapshot = interpreter.snapshot();
+	assert!(interpreter.is_string_mode());
+
+	assert!(Interpreter::<SimpleProgramState>::new("2;\n1", None, None).restore(snapshot.clone()).is_err());
+	assert!(Interpreter::<SimpleProgramState>::new("2;\n\n\"\"@", None, None).restore(snapshot.clone()).is_err());
+	assert!(Interpreter::<SimpleProgramState>::new("\n\n\"ab\"#", None, None).restore(snapshot).is_ok());
+
+	let file = "col-state 1\nposition 0 0 0\nstring 0 0\nsteps 0 0\nterminated 0\nrandom unseeded\ninput reader\ndecoder\nline 0\nnumber\nstacks 1\nstack 0 ";
+	assert!(Snapshot::<u32>::read_from(&b"col-state 2\n"[..]).is_err());
+	assert!(Snapshot::<u8>::read_from(format!("{}255\n", file).as_bytes()).is_ok());
+	assert!(Snapshot::<u8>::read_from(format!("{}256\n", file).as_bytes()).is_err());
+	assert!(Snapshot::<u8>::read_from(file.replace("decoder", "decoder peeked 0").as_bytes()).is_err());
+
+	// input which has been read, but not yet by the program, is saved too
+	let cases = [
+		(InputMode::Char, "_[$_]@", "a\u{e9}\u{20ac}b"),
+		(InputMode::Line, "_[$_]@", "ab\ncd\n"),
+		(InputMode::Number, "_[#A$_]@", "12 -34 5"),
+	];
+
+	for (mode, source, input) in &cases {
+		let expected = Interpreter::<SimpleProgramState>::new(source, Some(&mut input.as_bytes()), None)
+			.with_input_mode(*mode)
+			.with_output_events()
+			.run_and_take_output();
+
+		for split in 0..=input.len() {
+			let mut interpreter = Interpreter::<SimpleProgramState>::new(source, None, None)
+				.with_input_mode(*mode)
+				.with_suspending_input()
+				.with_output_events();
+
+			interpreter.push_input(&input.as_bytes()[..split]);
+			interpreter.run_for(100);
+			let mut output = interpreter.take_output();
+
+			let mut file = Vec::new();
+			interpreter.snapshot().write_to(&mut file).unwrap();
+
+			let mut restored = Interpreter::<SimpleProgramState>::new(source, None, None)
+				.with_input_mode(*mode)
+				.with_output_events();
+
+			restored.restore(Snapshot::read_from(file.as_slice()).unwrap()).unwrap();
+			restored.push_input(&input.as_bytes()[split..]);
+			restored.close_input();
+			output.extend(restored.run_and_take_output());
+
+			assert_eq!(output, expected, "{:?} split after {} bytes", mode, split);
+		}
+	}
+
+	// as is what's left of a replay
+	let mut reader = "xyz".as_bytes();
+	let mut recorded = Interpreter::<SimpleProgramState>::new("_$_$_$@", Some(&mut reader), None)
+		.with_recording(true)
+		.with_output_events();
+
+	recorded.run().unwrap();
+	let recording = recorded.recording().unwrap();
+
+	let mut interpreter = Interpreter::<SimpleProgramState>::new("_$_$_$@", None, None)
+		.with_replay(recording)
+		.with_output_events();
+
+	interpreter.run_for(2);
+	let mut restored = Interpreter::<SimpleProgramState>::new("_$_$_$@", None, None).with_output_events();
+	restored.restore(interpreter.snapshot()).unwrap();
+	assert_eq!(restored.run_and_take_output(), vec![OutputEvent::Char('y'), OutputEvent::Char('z')]);
+}
+
+#[test]
+fn test_stepping() {
+	let mut interpreter = Interpreter::<SimpleProgramState>::new("1~\"ab\"2;\n@\n^@", None, None);
+
+	assert!(matches!(interpreter.step(), ExecutionStatus::Running));
+	assert!(matches!(interpreter.step(), ExecutionStatus::Running));
+	assert_eq!((interpreter.remote_column(), interpreter.ip()), (1, 2));
+	assert!(matches!(interpreter.step(), ExecutionStatus::Running));
+	assert!(interpreter.is_string_mode());
+	assert!(matches!(interpreter.run_for(2), ExecutionStatus::Running));
+	assert!(!interpreter.is_string_mode());
+
+	assert!(matches!(interpreter.run_until(|i| i.local_column() == 2), ExecutionStatus::Running));
+	assert_eq!((interpreter.ip(), interpreter.steps()), (0, 7));
+	assert!(matches!(interpreter.run_for(100), ExecutionStatus::Terminated));
+	assert!(matches!(interpreter.step(), ExecutionStatus::Terminated));
+	assert_eq!(interpreter.steps(), 9);
+
+	for backend in &[Backend::Direct, Backend::Bytecode] {
+		let mut interpreter = Interpreter::<SimpleProgramState>::new("1/", None, None).with_backend(*backend);
+		assert!(matches!(interpreter.run_for(1000), ExecutionStatus::Running));
+		assert_eq!(interpreter.steps(), 1000);
+	}
+}
+
+#[test]
+fn test_waiting_for_input() {
+	use std::io::{self, ErrorKind};
+
+	/// Input which only becomes available after being asked for once.
+	struct Slow(Option<u8>);
+
+	impl Read for Slow {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			match self.0.take() {
+				Some(byte) => {
+					buf[0] = byte;
+					Ok(1)
+				},
+				None => {
+					self.0 = Some(b'a');
+					Err(ErrorKind::WouldBlock.into())
+				},
+			}
+		}
+	}
+
+	for backend in &[Backend::Direct, Backend::Bytecode] {
+		let mut input = Slow(None);
+		let mut output = Vec::new();
+		let mut interpreter = Interpreter::<SimpleProgramState>::new("5 1+ _# #@", Some(&mut input), Some(&mut output))
+			.with_backend(*backend);
+
+		assert!(matches!(interpreter.run_for(100), ExecutionStatus::NeedsInput));
+		assert_eq!((interpreter.ip(), interpreter.steps()), (3, 3));
+		assert!(matches!(interpreter.run_for(100), ExecutionStatus::Terminated));
+		assert_eq!(interpreter.steps(), 7);
+		drop(interpreter);
+
+		assert_eq!(String::from_utf8(output).unwrap(), "976");
+	}
+}
+
+#[test]
+fn test_pushed_input() {
+	for backend in &[Backend::Direct, Backend::Bytecode] {
+		let mut interpreter = Interpreter::<SimpleProgramState>::new("_[$_]xA$ \"ko\"p@", None, None)
+			.with_backend(*backend)
+			.with_suspending_input()
+			.with_output_events();
+
+		assert!(matches!(interpreter.run_for(100), ExecutionStatus::NeedsInput));
+		assert_eq!((interpreter.ip(), interpreter.steps()), (0, 0));
+
+		interpreter.push_input(b"hi");
+		assert!(matches!(interpreter.run_for(100), ExecutionStatus::NeedsInput));
+		assert_eq!(interpreter.take_output(), vec![OutputEvent::Char('h'), OutputEvent::Char('i')]);
+		assert_eq!(interpreter.take_output(), vec![]);
+
+		// the end of the input is zero, which leaves the loop
+		interpreter.close_input();
+		assert!(matches!(interpreter.run_for(100), ExecutionStatus::Terminated));
+		assert_eq!(interpreter.take_output(), vec![OutputEvent::Char('\n'), OutputEvent::String(String::from("ok"))]);
+	}
+
+	// reading the end of closed input can be a trap, rather than more waiting
+	let mut interpreter = Interpreter::<SimpleProgramState>::new("_@", None, None)
+		.with_strict_mode(StrictMode::all());
+
+	interpreter.close_input();
+	assert!(matches!(interpreter.run_for(100), ExecutionStatus::Error(_)));
+}
+
+#[test]
+fn test_send() {
+	use std::io::{self, Cursor, Write};
+	use std::sync::mpsc::{self, Sender};
+	use std::cell::Cell;
+	use std::rc::Rc;
+	use std::thread;
+	use crate::interpreter::Sendable;
+
+	/// Output sent to another thread as soon as it's written.
+	struct Channel(Sender<Vec<u8>>);
+
+	impl Write for Channel {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			self.0.send(buf.to_vec()).map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+			Ok(buf.len())
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	let (output, received) = mpsc::channel();
+	let (stepped, steps) = mpsc::channel();
+
+	let mut interpreter = Interpreter::<SimpleProgramState, Sendable>::from_owned(
+		parser::parse("_[$_]@"),
+		Some(Box::new(Cursor::new(b"abc".to_vec()))),
+		Some(Box::new(Channel(output))),
+	).with_boxed_step_callback(Box::new(move |_| stepped.send(()).unwrap()));
+
+	let running = thread::spawn(move || interpreter.run());
+	running.join().unwrap().unwrap();
+
+	// the senders are dropped along with the interpreter
+	assert_eq!(received.iter().flatten().collect::<Vec<u8>>(), b"abc");
+	assert_eq!(steps.iter().count(), 12);
+
+	// a borrowing interpreter can still hold what isn't `Send`
+	let steps = Rc::new(Cell::new(0));
+	let counted = Rc::clone(&steps);
+	let mut output = Vec::new();
+
+	Interpreter::<SimpleProgramState>::new("_[$_]@", Some(&mut "abc".as_bytes()), Some(&mut output))
+		.with_step_callback(&move |_| counted.set(counted.get() + 1))
+		.with_rng(rand::thread_rng())
+		.run()
+		.unwrap();
+
+	assert_eq!(output, b"abc");
+	assert_eq!(steps.get(), 12);
+
+	// as can its observer, handler and breakpoint conditions
+	use std::cell::RefCell;
+	use crate::interpreter::{Breakpoint, Input, IoHandler, Observer};
+	use crate::parser::Instruction;
+
+	struct Shared(Rc<RefCell<String>>);
+
+	impl IoHandler for Shared {
+		fn read(&mut self) -> io::Result<Input> {
+			Ok(Input::End)
+		}
+
+		fn print_char(&mut self, c: char) -> io::Result<()> {
+			self.0.borrow_mut().push(c);
+			Ok(())
+		}
+
+		fn print_number(&mut self, number: &str) -> io::Result<()> {
+			self.0.borrow_mut().push_str(number);
+			Ok(())
+		}
+	}
+
+	impl Observer<u32> for Shared {
+		fn before_instruction(&mut self, _column: u32, _ip: u32, instruction: Instruction) {
+			self.0.borrow_mut().push_str(&format!("{:?} ", instruction));
+		}
+	}
+
+	let (printed, observed) = (Rc::new(RefCell::new(String::new())), Rc::new(RefCell::new(String::new())));
+	let stop = Rc::new(Cell::new(2));
+	let condition = Rc::clone(&stop);
+
+	let mut interpreter = Interpreter::<SimpleProgramState>::new("1#2#@", None, None)
+		.with_io_handler(Shared(Rc::clone(&printed)))
+		.with_observer(Shared(Rc::clone(&observed)));
+	interpreter.add_breakpoint(Breakpoint::on(Instruction::PrintNumber).when(move |local, _| local.last() == Some(&condition.get())));
+
+	assert!(matches!(interpreter.run_for(100), ExecutionStatus::Stopped { .. }));
+	assert_eq!(*printed.borrow(), "1");
+	assert_eq!(*observed.borrow(), "Value(1) PrintNumber Value(2) ");
+}
+
+#[test]
+fn test_cell_types() {
+	assert_eq!(get_output_as::<u8>("1-# F1+F1+*# @"), "2550");
+	assert_eq!(get_output_as::<u16>("1-# @"), "65535");
+	assert_eq!(get_output_as::<u64>("1-# @"), "18446744073709551615");
+	assert_eq!(get_output_as::<i64>("1-# 15-2/# 77,# @"), "-1-2-8");
+}
+
+#[test]
+fn test_bigint() {
+	use crate::value::BigInt;
+
+	assert_eq!(get_output_as::<BigInt>("FF*F*F*F*F*F*F*F*F*# @"), "576650390625");
+	assert_eq!(get_output_as::<BigInt>("1-# 07-2/# 07-2%# 50/# 53,# 00,# @"), "-1-3-1061");
+
+	let big = |s: &str| s.parse::<BigInt>().unwrap();
+	let factorial = (1..=25).fold(BigInt::from_u32(1), |acc, n| acc.wrapping_mul(&BigInt::from_u32(n)));
+
+	assert_eq!(factorial.to_string(), "15511210043330985984000000");
+	assert_eq!(factorial.wrapping_div(&big("620448401733239439360000")).to_string(), "25");
+	assert_eq!(factorial.wrapping_rem(&big("-1000000000000000000007")).to_string(), "210043330985983891423");
+	assert_eq!(big("-18446744073709551616").wrapping_add(&big("18446744073709551617")).to_string(), "1");
+	assert!(big("-5") < big("3") && big("-5") < big("-3"));
+	assert_eq!(big("-1").to_u32(), u32::MAX);
+}
+
 #[test]
 fn test_logic() {
 	assert_eq!(get_output("55=# FA=# @"), "10"); // equality
@@ -47,7 +630,106 @@ fn test_logic() {
 
 #[test]
 fn test_io() {
+	assert_eq!(get_output_with_input("__$$@", "ab"), "ba");
+	assert_eq!(get_output_with_input("_#_#@", "a"), "970"); // end of input
+}
 
+#[test]
+fn test_charset() {
+	use crate::interpreter::{Charset, Unprintable};
+
+	/// Run a program with raw input bytes, resulting in the raw output bytes.
+	fn run(source: &str, input: &[u8], charset: Charset, unprintable: Unprintable) -> Result<Vec<u8>, String> {
+		let mut reader = input;
+		let mut output = Vec::new();
+
+		Interpreter::<SimpleProgramState>::new(source, Some(&mut reader), Some(&mut output))
+			.with_charset(charset)
+			.with_unprintable(unprintable)
+			.run()
+			.map_err(|e| e.to_string())?;
+
+		Ok(output)
+	}
+
+	let utf8 = |source, input| run(source, input, Charset::Utf8, Unprintable::Error);
+
+	// a whole character is read at once
+	assert_eq!(get_output_with_input("_#A$_#A$_#A$_$_#@", "é€😀x"), "233\n8364\n128512\nx0");
+	assert_eq!(utf8("_:[#A$_:]@", b"\xffa\xe2\x82b\xe2"), Ok(b"65533\n97\n65533\n98\n65533\n".to_vec()));
+	assert_eq!(utf8("_$@", "€".as_bytes()), Ok("€".as_bytes().to_vec()));
+	assert_eq!(utf8("1-$@", b""), Err("4294967295 is not a valid character at column 0, instruction 2".to_string()));
+	assert_eq!(run("1-$@", b"", Charset::Utf8, Unprintable::Replace), Ok("\u{fffd}".as_bytes().to_vec()));
+	assert_eq!(run("01-\"ab\"p@", b"", Charset::Utf8, Unprintable::Skip), Ok(b"ba".to_vec()));
+	assert!(run("01-\"ab\"p@", b"", Charset::Utf8, Unprintable::Error).is_err());
+
+	// unless told otherwise, `p` skips what isn't a character
+	assert_eq!(get_output("\"ih\"01-p@"), "hi");
+
+	// every byte is a character
+	assert_eq!(run("_#A$_#A$ FF*8+$@", "é".as_bytes(), Charset::Latin1, Unprintable::Error), Ok(b"195\n169\n\xe9".to_vec()));
+	assert!(run("88*4*$@", b"", Charset::Latin1, Unprintable::Error).is_err());
+	assert_eq!(run("88*4*$@", b"", Charset::Latin1, Unprintable::Replace), Ok(b"?".to_vec()));
+	assert_eq!(run("_#A$ 88*4*1+$ _$@", b"\xff\x80", Charset::Bytes, Unprintable::Error), Ok(b"255\n\x01\x80".to_vec()));
+
+	// a character can arrive in parts
+	for backend in &[Backend::Direct, Backend::Bytecode] {
+		let mut interpreter = Interpreter::<SimpleProgramState>::new("_#@", None, None)
+			.with_backend(*backend)
+			.with_output_events();
+
+		interpreter.push_input(b"\xc3");
+		assert!(matches!(interpreter.run_for(10), ExecutionStatus::NeedsInput));
+		interpreter.push_input(b"\xa9");
+		assert!(matches!(interpreter.run_for(10), ExecutionStatus::Terminated));
+		assert_eq!(interpreter.take_output(), vec![OutputEvent::Number(String::from("233"))]);
+	}
+}
+
+#[test]
+fn test_input_modes() {
+	use crate::interpreter::InputMode;
+
+	let numbers = |source, input| try_all_backends::<i64>(source, Some(input), &|i| i.with_input_mode(InputMode::Number));
+
+	assert_eq!(try_all_backends::<u32>("_#A$_#@", Some("a"), &|i| i.with_end_of_input(u32::MAX)), Ok("97\n4294967295".to_string()));
+	assert_eq!(numbers("_#A$_#A$_#A$_#@", " 12, -7 abc--3"), Ok("12\n-7\n-3\n0".to_string()));
+	assert_eq!(numbers("_#A$_#@", "4-5"), Ok("4\n5".to_string()));
+	assert_eq!(try_all_backends::<u8>("_#A$_#@", Some("-1 256 7"), &|i| i.with_input_mode(InputMode::Number)), Ok("0\n0".to_string()));
+
+	for mode in &[InputMode::Char, InputMode::Line] {
+		let mut interpreter = Interpreter::<SimpleProgramState>::new("_$_$_$_$@", None, None)
+			.with_input_mode(*mode)
+			.with_output_events();
+
+		interpreter.push_input(b"ab");
+		assert!(matches!(interpreter.run_for(100), ExecutionStatus::NeedsInput));
+
+		// only whole lines are read
+		let read = interpreter.take_output().len();
+		assert_eq!(read, if *mode == InputMode::Line { 0 } else { 2 });
+
+		interpreter.push_input(b"c\nd");
+		assert!(matches!(interpreter.run_for(100), ExecutionStatus::Terminated));
+		assert_eq!(interpreter.take_output().len() + read, 4);
+	}
+}
+
+#[test]
+fn test_errors() {
+	use crate::error::{Error, Location};
+
+	let run = |source| try_all_backends::<i64>(source, None, &|interpreter| interpreter);
+
+	assert_eq!(run("5# 1-$ @"), Err("-1 is not a valid character at column 0, instruction 4".to_string()));
+	assert_eq!(run(""), Err("the program has no columns".to_string()));
+
+	let mut sink = std::io::sink();
+	let mut interpreter = Interpreter::<SimpleProgramState<i64>>::new("1;\n1-$@", None, Some(&mut sink));
+	match interpreter.run() {
+		Err(error @ Error::InvalidCodePoint { .. }) => assert_eq!(error.location(), Some(Location { column: 1, ip: 2 })),
+		other => panic!("expected an invalid code point, got {:?}", other),
+	}
 }
 
 #[test]
@@ -62,12 +744,84 @@ fn test_stacks() {
 
 #[test]
 fn test_loops() {
-
+	assert_eq!(get_output("8[1-:#A$]@"), "7\n6\n5\n4\n3\n2\n1\n0\n");
+	assert_eq!(get_output("30[1#]2#@"), "2"); // skipped entirely
+	assert_eq!(get_output("2[1-2[1-:#]x:#]@"), "101100"); // nested
 }
 
 #[test]
 fn test_string_mode() {
+	assert_eq!(get_output("\"a b\"$$$@"), "b a");
+	assert_eq!(get_output("\"\"1#@"), "1"); // empty string
+	assert_eq!(get_output("\" r:2+p@"), "\" r:2+p@"); // wraps around the line
+}
 
+#[test]
+fn test_superinstructions() {
+	assert_eq!(get_output("92+# 3:1-[1-:]# @"), "110"); // value then binary, jumping into a fused pair
+	assert_eq!(get_output("5:^#1~5:^0~v# @"), "55"); // duplicate to remote
+	assert_eq!(get_output("1~7^v1~# 9~3^v9~# @"), "73"); // remote stack in and out of the program
+	assert_eq!(get_output("11#>;\nA$2~v0~v2~:^+::0~^# 9`[@]\n"), "1\n1\n2\n3\n5\n8\n13"); // fibonacci
+}
+
+#[test]
+fn test_backends_failing_alike() {
+	use rand::{Rng, SeedableRng};
+	use crate::interpreter::DefaultRng;
+	use crate::program::ProgramState;
+
+	type Outcome = (Result<(), String>, u32, u32, u64, Vec<(u32, Vec<u32>)>);
+
+	/// Everything about a program which failed or stopped that could differ between backends.
+	fn run(source: &str, backend: Backend, strict: StrictMode, limits: Limits) -> Outcome {
+		let mut interpreter = Interpreter::<SimpleProgramState>::new(source, None, None)
+			.with_backend(backend)
+			.with_strict_mode(strict)
+			.with_limits(limits);
+
+		let result = interpreter.run().map_err(|e| e.to_string());
+		let stacks = interpreter.state().stacks().into_iter().map(|(index, stack)| (index, stack.clone())).collect();
+
+		(result, interpreter.local_column(), interpreter.ip(), interpreter.steps(), stacks)
+	}
+
+	let limits = Limits { max_steps: Some(200), max_stack_depth: Some(6), max_cells: Some(10), max_columns: Some(6) };
+	let alike = |source: &str, strict: StrictMode| {
+		let direct = run(source, Backend::Direct, strict, limits);
+		assert_eq!(run(source, Backend::Bytecode, strict, limits), direct, "{:?}", source);
+		direct
+	};
+
+	// the failing instruction of a fused op is where the program stays, after the ones before it
+	let (result, _, ip, steps, _) = alike("E`C0&", StrictMode::all());
+	assert!(result.is_err());
+	assert_eq!((ip, steps), (1, 1));
+
+	let (_, _, ip, steps, stacks) = alike("1:^@", StrictMode::all());
+	assert_eq!((ip, steps, stacks), (2, 2, vec![(0, vec![1, 1])]));
+
+	let alphabet: Vec<char> = "0129ACEF+-*/%=`&|!:\\x c^v~s;<>.[]\"@\n".chars().collect();
+	let mut rng = DefaultRng::seed_from_u64(7);
+
+	for n in 0..2000 {
+		let len = rng.gen_range(1, 12);
+		let source: String = (0..len).map(|_| alphabet[rng.gen_range(0, alphabet.len())]).collect();
+
+		let _ = alike(&source, if n % 2 == 0 { StrictMode::all() } else { StrictMode::default() });
+	}
+}
+
+#[test]
+fn test_program_reuse() {
+	let program = parser::parse("_:#$@");
+
+	for input in &["a", "b"] {
+		let mut output = Vec::new();
+		Interpreter::<SimpleProgramState>::from_program(&program, Some(&mut input.as_bytes()), Some(&mut output))
+			.run()
+			.unwrap();
+		assert_eq!(String::from_utf8(output).unwrap(), format!("{}{}", input.as_bytes()[0], input));
+	}
 }
 
 #[test]
@@ -75,4 +829,423 @@ fn test_weird_chars() {
 
 }
 
-// TODO add more tests
\ No newline at end of file
+#[test]
+fn test_check() {
+	use crate::parser::{DiagnosticKind, Span};
+
+	let kinds = |source| parser::check(&parser::parse(source)).iter().map(|d| d.kind).collect::<Vec<_>>();
+
+	assert_eq!(kinds("8[1-:#A$]@"), vec![]);
+	assert_eq!(kinds("]1[@"), vec![DiagnosticKind::UnmatchedRightBracket, DiagnosticKind::UnmatchedLeftBracket]);
+	assert_eq!(kinds("\"a\"\"b@"), vec![DiagnosticKind::UnterminatedString]);
+	assert_eq!(kinds("\"@\"1[]"), vec![DiagnosticKind::MissingTerminator]);
+	assert_eq!(kinds(include_str!("../examples/quine.col")), vec![]);
+	assert_eq!(kinds("1;\n  \n"), vec![DiagnosticKind::EmptyColumn, DiagnosticKind::MissingTerminator]);
+
+	let diagnostics = parser::check(&parser::parse("@\n 12[3"));
+	assert_eq!(diagnostics[0].span, Some(Span { line: 1, start: 3, end: 4 }));
+}
+
+#[test]
+fn test_output_buffering() {
+	use std::io::{self, Write};
+	use std::sync::{Arc, Mutex};
+
+	/// Keeps every write separately.
+	#[derive(Clone, Default)]
+	struct Chunks(Arc<Mutex<Vec<Vec<u8>>>>);
+
+	impl Write for Chunks {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			self.0.lock().unwrap().push(buf.to_vec());
+			Ok(buf.len())
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	/// Notes what had been written when the program reads.
+	struct Prompted(Chunks, Vec<u8>);
+
+	impl Read for Prompted {
+		fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+			self.1 = self.0 .0.lock().unwrap().concat();
+			(&b"a"[..]).read(buf)
+		}
+	}
+
+	for backend in &[Backend::Direct, Backend::Bytecode] {
+		let run = |source: &str, size| {
+			let chunks = Chunks::default();
+			let mut writer = chunks.clone();
+			let mut reader = Prompted(chunks.clone(), Vec::new());
+
+			let result = Interpreter::<SimpleProgramState>::new(source, Some(&mut reader), Some(&mut writer))
+				.with_backend(*backend)
+				.with_output_buffer(size)
+				.run();
+
+			let chunks = chunks.0.lock().unwrap().clone();
+			(result.is_ok(), chunks, reader.1)
+		};
+
+		// the prompt is written before the input is read
+		let (ok, chunks, prompt) = run("\"?tahw\"p _$ \"!\"p@", 1024);
+		assert!(ok);
+		assert_eq!(prompt, b"what?");
+		assert_eq!(chunks, vec![b"what?".to_vec(), b"a!".to_vec()]);
+
+		// unbuffered output is written as it's printed
+		let (_, chunks, _) = run("\"?tahw\"p _$ \"!\"p@", 0);
+		assert_eq!(chunks, vec![b"what?".to_vec(), b"a".to_vec(), b"!".to_vec()]);
+
+		// a full buffer is written without waiting
+		let (_, chunks, _) = run("\"dcba\"$$$$@", 2);
+		assert_eq!(chunks, vec![b"ab".to_vec(), b"cd".to_vec()]);
+
+		// whatever was printed before an error is still written
+		let (ok, chunks, _) = run("\"ih\"$$ 1-$@", 1024);
+		assert!(!ok);
+		assert_eq!(chunks, vec![b"hi".to_vec()]);
+	}
+
+	// single steps keep the output buffered, and it's written once the run loop ends
+	let chunks = Chunks::default();
+	let mut writer = chunks.clone();
+	let mut interpreter = Interpreter::<SimpleProgramState>::new("\"dcba\"$$$$ 1[]@", None, Some(&mut writer));
+
+	for _ in 0..10 {
+		assert!(matches!(interpreter.step(), ExecutionStatus::Running));
+	}
+
+	assert!(chunks.0.lock().unwrap().is_empty());
+
+	assert!(matches!(interpreter.run_until(|interpreter| interpreter.steps() == 20), ExecutionStatus::Running));
+	assert_eq!(*chunks.0.lock().unwrap(), vec![b"abcd".to_vec()]);
+}
+
+#[test]
+fn test_observer() {
+	use crate::interpreter::{Input, Observer};
+	use crate::parser::Instruction;
+
+	#[derive(Default)]
+	struct Events(Vec<String>, u64);
+
+	impl Observer<i64> for Events {
+		fn before_instruction(&mut self, _column: u32, _ip: u32, _instruction: Instruction) {
+			self.1 += 1;
+		}
+
+		fn on_push(&mut self, column: u32, value: &i64) {
+			self.0.push(format!("push {} {}", column, value));
+		}
+
+		fn on_pop(&mut self, column: u32, value: &i64) {
+			self.0.push(format!("pop {} {}", column, value));
+		}
+
+		fn on_column_switch(&mut self, from: u32, to: u32) {
+			self.0.push(format!("column {} {}", from, to));
+		}
+
+		fn on_remote_switch(&mut self, from: u32, to: u32) {
+			self.0.push(format!("remote {} {}", from, to));
+		}
+
+		fn on_input(&mut self, input: &Input) {
+			self.0.push(format!("input {:?}", input));
+		}
+
+		fn on_output(&mut self, output: &OutputEvent) {
+			self.0.push(format!("output {:?}", output));
+		}
+
+		fn on_terminate(&mut self) {
+			self.0.push(String::from("terminate"));
+		}
+	}
+
+	let mut events = Events::default();
+	let mut input = "a".as_bytes();
+	let mut output = Vec::new();
+
+	let mut interpreter = Interpreter::<SimpleProgramState<i64>>::new("2:+_$1;\n0~9^\"a\"@", Some(&mut input), Some(&mut output))
+		.with_backend(Backend::Bytecode)
+		.with_observer(&mut events);
+
+	interpreter.run().unwrap();
+	let steps = interpreter.steps();
+	drop(interpreter);
+
+	assert_eq!(events.1, steps);
+	assert_eq!(events.0, vec![
+		"push 0 2", "push 0 2", "pop 0 2", "pop 0 2", "push 0 4",
+		"input Char(97)", "push 0 97", "output Char('a')", "pop 0 97",
+		"push 0 1", "pop 0 1", "column 0 1",
+		"push 1 0", "pop 1 0", "remote 0 0",
+		"push 1 9", "pop 1 9", "push 0 9",
+		"push 1 97", "terminate",
+	]);
+
+	// an instruction waiting for input is only seen once, however often it's retried
+	let mut events = Events::default();
+	let mut interpreter = Interpreter::<SimpleProgramState<i64>>::new("_$@", None, None)
+		.with_suspending_input()
+		.with_output_events()
+		.with_observer(&mut events);
+
+	assert!(matches!(interpreter.run_for(10), ExecutionStatus::NeedsInput));
+	assert!(matches!(interpreter.run_for(10), ExecutionStatus::NeedsInput));
+	interpreter.push_input(b"a");
+	assert!(matches!(interpreter.run_for(10), ExecutionStatus::Terminated));
+
+	let steps = interpreter.steps();
+	drop(interpreter);
+	assert_eq!(events.1, steps);
+}
+
+#[test]
+fn test_profiler() {
+	use crate::interpreter::Profiler;
+
+	let program = parser::parse("3[1-:]1;\n2;\n.1+:4=[x0]x;\n\"a\"@");
+	let mut profiler = Profiler::default();
+
+	let mut interpreter = Interpreter::<SimpleProgramState>::from_program(&program, None, None)
+		.with_observer(&mut profiler);
+
+	interpreter.run().unwrap();
+	let steps = interpreter.steps();
+	drop(interpreter);
+
+	assert_eq!(profiler.steps(), steps);
+	assert_eq!(profiler.count(0, 2), 3);
+	assert_eq!(profiler.count(3, 0), 2);
+	assert_eq!(profiler.transitions(), vec![(0, 1, 1), (1, 2, 1), (2, 3, 1)]);
+	assert_eq!(profiler.hot_spots(1), vec![(0, 2, 3)]);
+	assert!(profiler.instruction_counts(&program).contains(&(String::from("Value"), 8)));
+
+	let mut folded = Vec::new();
+	profiler.write_folded(&mut folded).unwrap();
+	assert_eq!(String::from_utf8(folded).unwrap(), "column 0 16\ncolumn 0;column 1 2\ncolumn 0;column 1;column 2 9\ncolumn 0;column 1;column 2;column 3 3\n");
+
+	// going back to a column keeps the chain from growing
+	let program = parser::parse("1;\n2;\n0;");
+	let mut profiler = Profiler::default();
+
+	Interpreter::<SimpleProgramState>::from_program(&program, None, None)
+		.with_observer(&mut profiler)
+		.with_limits(Limits { max_steps: Some(60), ..Limits::default() })
+		.run()
+		.unwrap_err();
+
+	let mut folded = Vec::new();
+	profiler.write_folded(&mut folded).unwrap();
+	assert_eq!(String::from_utf8(folded).unwrap(), "column 0 20\ncolumn 0;column 1 20\ncolumn 0;column 1;column 2 20\n");
+}
+
+#[test]
+fn test_coverage() {
+	use crate::interpreter::Coverage;
+
+	let program = parser::parse("_[1-:]x1;\n\"v\"@\n@");
+	let mut coverage = Coverage::new(&program);
+
+	for input in &["", "b"] {
+		Interpreter::<SimpleProgramState>::from_program(&program, Some(&mut input.as_bytes()), None)
+			.with_observer(&mut coverage)
+			.run()
+			.unwrap();
+	}
+
+	assert_eq!(coverage.count(0, 0), 2);
+	assert_eq!(coverage.count(0, 2), 98);
+	assert_eq!(coverage.branch(0, 1), Some((1, 1)));
+	assert_eq!(coverage.branch(0, 2), None);
+	assert!(coverage.is_entered(1));
+	assert!(!coverage.is_entered(2));
+
+	// the `v` in the string isn't an instruction which can be executed
+	assert!(!coverage.is_code(1, 1));
+	assert!(coverage.is_code(2, 0));
+
+	let mut lcov = Vec::new();
+	coverage.write_lcov("test.col", &mut lcov).unwrap();
+
+	let lcov = String::from_utf8(lcov).unwrap();
+	assert!(lcov.contains("BRDA:1,1,0,1\nBRDA:1,1,1,1\n"));
+	assert!(lcov.contains("DA:1,98\n"));
+	assert!(lcov.contains("DA:3,0\n"));
+	assert!(!lcov.contains("CA:2,1,"));
+
+	// reading the file back adds to what was there
+	let mut merged = Coverage::new(&program);
+	merged.merge_lcov(lcov.as_bytes()).unwrap();
+	merged.merge_lcov(lcov.as_bytes()).unwrap();
+
+	assert_eq!(merged.count(0, 2), 196);
+	assert_eq!(merged.branch(0, 1), Some((2, 2)));
+
+	// a file for another program is rejected
+	let mut other = Coverage::new(&parser::parse("@"));
+	assert!(other.merge_lcov(lcov.as_bytes()).is_err());
+	assert_eq!(other.count(0, 0), 0);
+
+	let mut report = Vec::new();
+	coverage.write_report(&mut report).unwrap();
+
+	let report = String::from_utf8(report).unwrap();
+	assert!(report.contains("   2 | @\n     | !\n"));
+	assert!(report.contains("columns never entered: 2\n"));
+
+	// code after a `@` is never executed, but it is still code, and the marks keep tabs
+	let program = parser::parse("1@\t\"1\"2");
+	let mut coverage = Coverage::new(&program);
+
+	Interpreter::<SimpleProgramState>::from_program(&program, None, None)
+		.with_observer(&mut coverage)
+		.run()
+		.unwrap();
+
+	assert!(coverage.is_code(0, 2));
+	assert!(!coverage.is_code(0, 3));
+	assert!(coverage.is_code(0, 5));
+
+	let mut report = Vec::new();
+	coverage.write_report(&mut report).unwrap();
+
+	let report = String::from_utf8(report).unwrap();
+	assert!(report.contains("   0 | 1@\t\"1\"2\n     |   \t!  !\n"));
+	assert_eq!(program.columns()[0].indent(4), "  \t ");
+	assert!(report.contains("instructions executed: 2 of 4 (50.0%)\n"));
+}
+
+#[test]
+fn test_set_stack() {
+	use crate::program::ProgramState;
+
+	let mut output = Vec::new();
+
+	let mut interpreter = Interpreter::<SimpleProgramState>::new("3~v#v#@", None, Some(&mut output))
+		.with_limits(Limits { max_cells: Some(3), ..Limits::default() });
+
+	// the stack doesn't have to exist yet
+	interpreter.set_stack(3, vec![7, 8]);
+	assert_eq!(*interpreter.state().nth(3).unwrap().borrow().values(), vec![7, 8]);
+
+	interpreter.run().unwrap();
+	drop(interpreter);
+	assert_eq!(output, b"87");
+
+	// the values set count towards the limits
+	let mut interpreter = Interpreter::<SimpleProgramState>::new("1@", None, None)
+		.with_limits(Limits { max_cells: Some(3), ..Limits::default() });
+
+	interpreter.set_stack(0, vec![1, 2, 3]);
+	assert!(interpreter.run().is_err());
+}
+
+#[test]
+fn test_breakpoints() {
+	use crate::interpreter::{Breakpoint, StopReason};
+	use crate::parser::Instruction;
+	use crate::program::ProgramState;
+
+	fn stopped_at<P: ProgramState>(interpreter: &mut Interpreter<P>) -> Option<(u32, u32)> {
+		match interpreter.run_for(1000) {
+			ExecutionStatus::Stopped { reason: StopReason::Breakpoint { location, .. } } => Some((location.column, location.ip)),
+			_ => None,
+		}
+	}
+
+	let mut interpreter = Interpreter::<SimpleProgramState>::new("1;\n2;\n@", None, None)
+		.with_backend(Backend::Bytecode);
+
+	let at = interpreter.add_breakpoint(Breakpoint::at(1, 1));
+	interpreter.add_breakpoint(Breakpoint::on(Instruction::Terminate));
+
+	// stopping happens before the instruction, and resuming doesn't stop there again
+	assert_eq!(stopped_at(&mut interpreter), Some((1, 1)));
+	assert_eq!((interpreter.local_column(), interpreter.ip(), interpreter.steps()), (1, 1, 3));
+	assert_eq!(stopped_at(&mut interpreter), Some((2, 0)));
+	assert!(matches!(interpreter.step(), ExecutionStatus::Terminated));
+
+	assert!(interpreter.remove_breakpoint(at));
+	assert!(!interpreter.remove_breakpoint(at));
+
+	// conditions see the local stack
+	let mut interpreter = Interpreter::<SimpleProgramState>::new("5[1-:]@", None, None);
+	interpreter.add_breakpoint(Breakpoint::at(0, 5).when(|local, _| local.last() == Some(&2)));
+
+	assert_eq!(stopped_at(&mut interpreter), Some((0, 5)));
+	assert_eq!(*interpreter.state().nth(0).unwrap().borrow().values(), vec![4, 3, 2, 2]);
+	assert!(matches!(interpreter.run_for(1000), ExecutionStatus::Terminated));
+
+	// a breakpoint on `_` waiting for input isn't stopped at again
+	let mut interpreter = Interpreter::<SimpleProgramState>::new("_@", None, None)
+		.with_suspending_input();
+	interpreter.add_breakpoint(Breakpoint::on(Instruction::Input));
+
+	assert_eq!(stopped_at(&mut interpreter), Some((0, 0)));
+	assert!(matches!(interpreter.run_for(10), ExecutionStatus::NeedsInput));
+	interpreter.close_input();
+	assert!(matches!(interpreter.run_for(10), ExecutionStatus::Terminated));
+
+	// entering a column with `;` stops at its first instruction, but starting there doesn't
+	let mut interpreter = Interpreter::<SimpleProgramState>::new("1;\n0;", None, None);
+	interpreter.add_breakpoint(Breakpoint::entering(1));
+	interpreter.add_breakpoint(Breakpoint::entering(0));
+
+	assert_eq!(stopped_at(&mut interpreter), Some((1, 0)));
+	assert_eq!(stopped_at(&mut interpreter), Some((0, 0)));
+	assert_eq!(stopped_at(&mut interpreter), Some((1, 0)));
+	assert_eq!(interpreter.steps(), 6);
+
+	// running goes past them
+	let mut interpreter = Interpreter::<SimpleProgramState>::new("1@", None, None);
+	interpreter.add_breakpoint(Breakpoint::at(0, 0));
+	interpreter.run().unwrap();
+	assert_eq!(interpreter.steps(), 2);
+}
+
+#[test]
+fn test_watchpoints() {
+	use crate::interpreter::{StopReason, Watchpoint};
+
+	/// Where each watchpoint stopped the program, until it terminates.
+	fn stops(source: &str, watchpoint: Watchpoint) -> Vec<(u32, u32)> {
+		let mut interpreter = Interpreter::<SimpleProgramState>::new(source, None, None);
+		interpreter.add_watchpoint(watchpoint);
+
+		let mut stops = Vec::new();
+
+		loop {
+			match interpreter.run_for(1000) {
+				ExecutionStatus::Stopped { reason: StopReason::Watchpoint { location, .. } } => stops.push((location.column, location.ip)),
+				ExecutionStatus::Terminated => return stops,
+				status => panic!("unexpected status {:?}", status),
+			}
+		}
+	}
+
+	assert_eq!(stops("12+1~^\"ab\"@\n", Watchpoint::Push(0)), vec![(0, 0), (0, 1), (0, 2), (0, 3), (0, 6), (0, 6)]);
+	assert_eq!(stops("12+1~^\"ab\"@\n", Watchpoint::Push(1)), vec![(0, 5)]);
+	assert_eq!(stops("12+1~^\"ab\"@\n", Watchpoint::Pop(0)), vec![(0, 2), (0, 4), (0, 5)]);
+	assert_eq!(stops("$12cc@", Watchpoint::Clear(0)), vec![(0, 3)]);
+	assert_eq!(stops("12p@", Watchpoint::Clear(1)), vec![]);
+
+	// without a writer, `p` leaves the stack alone
+	assert_eq!(stops("12p@", Watchpoint::Clear(0)), vec![]);
+
+	// `s` only swaps with a separate remote stack
+	assert_eq!(stops("s1~s@\n", Watchpoint::Swap(0)), vec![(0, 3)]);
+	assert_eq!(stops("s1~s@\n", Watchpoint::Swap(1)), vec![(0, 3)]);
+
+	// only growing past the depth stops, not staying there
+	assert_eq!(stops("111x1:@", Watchpoint::Depth(0, 2)), vec![(0, 2), (0, 4)]);
+}
+
+// TODO add more tests
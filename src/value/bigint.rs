@@ -0,0 +1,405 @@
+//! An arbitrary-precision integer, so programs can work with numbers of any size.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+
+use super::Value;
+
+/// A signed integer of unbounded size.
+///
+/// Arithmetic never overflows, so the wrapping, checked and saturating operations of
+/// [`Value`](trait.Value.html) are all exact.
+/// Division truncates towards zero, and as with every other value type, dividing by zero results
+/// in zero.
+///
+/// Since there is no fixed width to invert the bits of, NAND works on the magnitudes of the
+/// operands, inverting only as many bits as the wider magnitude has (but at least one). The result
+/// is never negative.
+///
+/// # Example
+///
+/// ```
+/// use col::value::{BigInt, Value};
+///
+/// let big = BigInt::from_u32(u32::MAX).wrapping_mul(&BigInt::from_u32(16));
+///
+/// assert_eq!(big.to_string(), "68719476720");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BigInt {
+	/// Whether the number is below zero, which is never the case for zero itself
+	negative: bool,
+	/// Little endian limbs of the magnitude, without any trailing zero limbs
+	magnitude: Vec<u32>,
+}
+
+/// Parse error for [`BigInt`](struct.BigInt.html).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseBigIntError;
+
+impl BigInt {
+	fn from_parts(negative: bool, mut magnitude: Vec<u32>) -> BigInt {
+		while magnitude.last() == Some(&0) {
+			magnitude.pop();
+		}
+
+		BigInt {
+			negative: negative && !magnitude.is_empty(),
+			magnitude,
+		}
+	}
+
+	/// Whether or not the number is below zero.
+	pub fn is_negative(&self) -> bool {
+		self.negative
+	}
+
+	fn negate(mut self) -> BigInt {
+		self.negative = !self.negative && !self.magnitude.is_empty();
+		self
+	}
+}
+
+impl From<u64> for BigInt {
+	fn from(value: u64) -> Self {
+		BigInt::from_parts(false, vec![value as u32, (value >> 32) as u32])
+	}
+}
+
+impl From<i64> for BigInt {
+	fn from(value: i64) -> Self {
+		let magnitude = BigInt::from(value.unsigned_abs());
+
+		if value < 0 { magnitude.negate() } else { magnitude }
+	}
+}
+
+impl Value for BigInt {
+	const NAME: &'static str = "bigint";
+
+	fn from_u32(value: u32) -> Self {
+		BigInt::from_parts(false, vec![value])
+	}
+
+	fn to_u32(&self) -> u32 {
+		let low = self.magnitude.first().copied().unwrap_or(0);
+
+		// the same bits as a two's complement number would have
+		if self.negative { low.wrapping_neg() } else { low }
+	}
+
+	fn to_char(&self) -> Option<char> {
+		match self.magnitude.len() {
+			0 => Some('\0'),
+			1 if !self.negative => std::char::from_u32(self.magnitude[0]),
+			_ => None,
+		}
+	}
+
+	fn is_zero(&self) -> bool {
+		self.magnitude.is_empty()
+	}
+
+	fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+		BigInt::from_u32(rng.gen())
+	}
+
+	fn wrapping_add(&self, other: &Self) -> Self {
+		if self.negative == other.negative {
+			return BigInt::from_parts(self.negative, add_magnitudes(&self.magnitude, &other.magnitude));
+		}
+
+		// the signs differ, so subtract the smaller magnitude from the larger one
+		match compare_magnitudes(&self.magnitude, &other.magnitude) {
+			Ordering::Less => BigInt::from_parts(other.negative, sub_magnitudes(&other.magnitude, &self.magnitude)),
+			_ => BigInt::from_parts(self.negative, sub_magnitudes(&self.magnitude, &other.magnitude)),
+		}
+	}
+
+	fn wrapping_sub(&self, other: &Self) -> Self {
+		self.wrapping_add(&other.clone().negate())
+	}
+
+	fn wrapping_mul(&self, other: &Self) -> Self {
+		BigInt::from_parts(self.negative != other.negative, mul_magnitudes(&self.magnitude, &other.magnitude))
+	}
+
+	fn wrapping_div(&self, other: &Self) -> Self {
+		if other.is_zero() {
+			return BigInt::default();
+		}
+
+		let (quotient, _) = divide_magnitudes(&self.magnitude, &other.magnitude);
+		BigInt::from_parts(self.negative != other.negative, quotient)
+	}
+
+	fn wrapping_rem(&self, other: &Self) -> Self {
+		if other.is_zero() {
+			return BigInt::default();
+		}
+
+		// the remainder takes the sign of the dividend, like the primitive types
+		let (_, remainder) = divide_magnitudes(&self.magnitude, &other.magnitude);
+		BigInt::from_parts(self.negative, remainder)
+	}
+
+	fn checked_add(&self, other: &Self) -> Option<Self> {
+		Some(self.wrapping_add(other))
+	}
+
+	fn checked_sub(&self, other: &Self) -> Option<Self> {
+		Some(self.wrapping_sub(other))
+	}
+
+	fn checked_mul(&self, other: &Self) -> Option<Self> {
+		Some(self.wrapping_mul(other))
+	}
+
+	fn checked_div(&self, other: &Self) -> Option<Self> {
+		Some(self.wrapping_div(other))
+	}
+
+	fn checked_rem(&self, other: &Self) -> Option<Self> {
+		Some(self.wrapping_rem(other))
+	}
+
+	fn saturating_add(&self, other: &Self) -> Self {
+		self.wrapping_add(other)
+	}
+
+	fn saturating_sub(&self, other: &Self) -> Self {
+		self.wrapping_sub(other)
+	}
+
+	fn saturating_mul(&self, other: &Self) -> Self {
+		self.wrapping_mul(other)
+	}
+
+	fn saturating_div(&self, other: &Self) -> Self {
+		self.wrapping_div(other)
+	}
+
+	fn nand(&self, other: &Self) -> Self {
+		let bits = bit_length(&self.magnitude).max(bit_length(&other.magnitude)).max(1);
+		let len = bits.div_ceil(32);
+
+		let mut magnitude: Vec<u32> = (0..len)
+			.map(|i| {
+				let a = self.magnitude.get(i).copied().unwrap_or(0);
+				let b = other.magnitude.get(i).copied().unwrap_or(0);
+				!(a & b)
+			})
+			.collect();
+
+		// only keep as many bits as the wider operand
+		let partial = bits % 32;
+		if partial != 0 {
+			magnitude[len - 1] &= (1 << partial) - 1;
+		}
+
+		BigInt::from_parts(false, magnitude)
+	}
+}
+
+impl Ord for BigInt {
+	fn cmp(&self, other: &Self) -> Ordering {
+		match (self.negative, other.negative) {
+			(false, true) => Ordering::Greater,
+			(true, false) => Ordering::Less,
+			(false, false) => compare_magnitudes(&self.magnitude, &other.magnitude),
+			(true, true) => compare_magnitudes(&other.magnitude, &self.magnitude),
+		}
+	}
+}
+
+impl PartialOrd for BigInt {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl fmt::Display for BigInt {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		if self.magnitude.is_empty() {
+			return f.write_str("0");
+		}
+
+		// split into chunks of nine decimal digits, least significant first
+		let mut chunks = Vec::new();
+		let mut rest = self.magnitude.clone();
+
+		while !rest.is_empty() {
+			let (quotient, remainder) = divide_small(&rest, 1_000_000_000);
+			chunks.push(remainder);
+			rest = quotient;
+		}
+
+		if self.negative {
+			f.write_str("-")?;
+		}
+
+		write!(f, "{}", chunks.pop().unwrap())?;
+
+		for chunk in chunks.iter().rev() {
+			write!(f, "{:09}", chunk)?;
+		}
+
+		Ok(())
+	}
+}
+
+impl FromStr for BigInt {
+	type Err = ParseBigIntError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (negative, digits) = match s.strip_prefix('-') {
+			Some(digits) => (true, digits),
+			None => (false, s),
+		};
+
+		if digits.is_empty() {
+			return Err(ParseBigIntError);
+		}
+
+		let ten = BigInt::from_u32(10);
+		let mut value = BigInt::default();
+
+		for c in digits.chars() {
+			let digit = c.to_digit(10).ok_or(ParseBigIntError)?;
+			value = value.wrapping_mul(&ten).wrapping_add(&BigInt::from_u32(digit));
+		}
+
+		Ok(if negative { value.negate() } else { value })
+	}
+}
+
+impl fmt::Display for ParseBigIntError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str("invalid integer")
+	}
+}
+
+impl std::error::Error for ParseBigIntError {}
+
+fn bit_length(magnitude: &[u32]) -> usize {
+	match magnitude.last() {
+		Some(last) => magnitude.len() * 32 - last.leading_zeros() as usize,
+		None => 0,
+	}
+}
+
+fn compare_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+	a.len().cmp(&b.len())
+		.then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+	let mut result = Vec::with_capacity(a.len().max(b.len()) + 1);
+	let mut carry = 0;
+
+	for i in 0..a.len().max(b.len()) {
+		let sum = a.get(i).copied().unwrap_or(0) as u64 + b.get(i).copied().unwrap_or(0) as u64 + carry;
+		result.push(sum as u32);
+		carry = sum >> 32;
+	}
+
+	result.push(carry as u32);
+	result
+}
+
+/// Subtract `b` from `a`, where `a` is at least as large as `b`.
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+	let mut result = Vec::with_capacity(a.len());
+	let mut borrow = 0;
+
+	for (i, limb) in a.iter().enumerate() {
+		let (difference, overflow_a) = limb.overflowing_sub(b.get(i).copied().unwrap_or(0));
+		let (difference, overflow_b) = difference.overflowing_sub(borrow);
+		result.push(difference);
+		borrow = (overflow_a || overflow_b) as u32;
+	}
+
+	result
+}
+
+fn mul_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+	let mut result = vec![0u32; a.len() + b.len()];
+
+	for (i, x) in a.iter().enumerate() {
+		let mut carry = 0u64;
+
+		for (j, y) in b.iter().enumerate() {
+			let product = *x as u64 * *y as u64 + result[i + j] as u64 + carry;
+			result[i + j] = product as u32;
+			carry = product >> 32;
+		}
+
+		result[i + b.len()] = carry as u32;
+	}
+
+	result
+}
+
+/// Divide a magnitude by a single limb, returning the quotient and remainder.
+fn divide_small(a: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+	let mut quotient = vec![0; a.len()];
+	let mut remainder = 0u64;
+
+	for i in (0..a.len()).rev() {
+		let current = (remainder << 32) | a[i] as u64;
+		quotient[i] = (current / divisor as u64) as u32;
+		remainder = current % divisor as u64;
+	}
+
+	while quotient.last() == Some(&0) {
+		quotient.pop();
+	}
+
+	(quotient, remainder as u32)
+}
+
+/// Divide two magnitudes, where `b` is non-zero, returning the quotient and remainder.
+fn divide_magnitudes(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+	if b.len() == 1 {
+		let (quotient, remainder) = divide_small(a, b[0]);
+		return (quotient, vec![remainder]);
+	}
+
+	if compare_magnitudes(a, b) == Ordering::Less {
+		return (Vec::new(), a.to_vec());
+	}
+
+	// plain long division, one bit at a time
+	let mut quotient = vec![0u32; a.len()];
+	let mut remainder: Vec<u32> = Vec::new();
+
+	for bit in (0..bit_length(a)).rev() {
+		// shift the remainder left by one, bringing in the next bit of `a`
+		let mut carry = (a[bit / 32] >> (bit % 32)) & 1;
+
+		for limb in remainder.iter_mut() {
+			let next = *limb >> 31;
+			*limb = (*limb << 1) | carry;
+			carry = next;
+		}
+
+		if carry != 0 {
+			remainder.push(carry);
+		}
+
+		if compare_magnitudes(&remainder, b) != Ordering::Less {
+			remainder = sub_magnitudes(&remainder, b);
+
+			while remainder.last() == Some(&0) {
+				remainder.pop();
+			}
+
+			quotient[bit / 32] |= 1 << (bit % 32);
+		}
+	}
+
+	(quotient, remainder)
+}
@@ -0,0 +1,184 @@
+//! The values held by col stacks.
+//!
+//! The value type is left up to the implementation, and defaults to `u32`. Any type implementing
+//! [`Value`](trait.Value.html) can be used as the cell type of a program state, for example
+//! `SimpleProgramState<u8>`.
+//!
+//! [`BigInt`](struct.BigInt.html) can be used for values which never overflow.
+
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+
+use rand::Rng;
+
+mod bigint;
+
+pub use bigint::BigInt;
+
+/// A value which can be stored on a col stack.
+///
+/// All arithmetic follows col's rule that failing operations yield zero, so dividing by zero
+/// results in zero rather than a panic.
+///
+/// Values are parsed back from how they are displayed when a
+/// [snapshot](../interpreter/struct.Snapshot.html) is read.
+pub trait Value: Clone + Default + PartialEq + PartialOrd + Debug + Display + FromStr {
+	/// The name of the type, such as `u32`, which stays the same between builds.
+	const NAME: &'static str;
+
+	/// Convert from a `u32`, wrapping if it doesn't fit.
+	fn from_u32(value: u32) -> Self;
+
+	/// Convert to a `u32`, wrapping if it doesn't fit. Used for column indices.
+	fn to_u32(&self) -> u32;
+
+	/// Convert from a character, wrapping its code point if it doesn't fit.
+	fn from_char(c: char) -> Self {
+		Self::from_u32(c as u32)
+	}
+
+	/// Convert to a character, if the value is a valid Unicode scalar value.
+	fn to_char(&self) -> Option<char>;
+
+	/// Convert from a boolean, as `1` or `0`.
+	fn from_bool(value: bool) -> Self {
+		Self::from_u32(value as u32)
+	}
+
+	/// Whether or not the value is zero.
+	fn is_zero(&self) -> bool {
+		*self == Self::default()
+	}
+
+	/// Generate a random value.
+	fn random<R: Rng + ?Sized>(rng: &mut R) -> Self;
+
+	fn wrapping_add(&self, other: &Self) -> Self;
+
+	fn wrapping_sub(&self, other: &Self) -> Self;
+
+	fn wrapping_mul(&self, other: &Self) -> Self;
+
+	/// Divide, resulting in zero if `other` is zero.
+	fn wrapping_div(&self, other: &Self) -> Self;
+
+	/// Remainder of the division, resulting in zero if `other` is zero.
+	fn wrapping_rem(&self, other: &Self) -> Self;
+
+	/// Add, resulting in `None` if the result overflows.
+	fn checked_add(&self, other: &Self) -> Option<Self>;
+
+	/// Subtract, resulting in `None` if the result overflows.
+	fn checked_sub(&self, other: &Self) -> Option<Self>;
+
+	/// Multiply, resulting in `None` if the result overflows.
+	fn checked_mul(&self, other: &Self) -> Option<Self>;
+
+	/// Divide, resulting in `None` if the result overflows, and zero if `other` is zero.
+	fn checked_div(&self, other: &Self) -> Option<Self>;
+
+	/// Remainder of the division, resulting in `None` if the division overflows, and zero if
+	/// `other` is zero.
+	fn checked_rem(&self, other: &Self) -> Option<Self>;
+
+	fn saturating_add(&self, other: &Self) -> Self;
+
+	fn saturating_sub(&self, other: &Self) -> Self;
+
+	fn saturating_mul(&self, other: &Self) -> Self;
+
+	/// Divide, saturating if the result overflows and resulting in zero if `other` is zero.
+	fn saturating_div(&self, other: &Self) -> Self;
+
+	/// Bitwise NAND.
+	fn nand(&self, other: &Self) -> Self;
+}
+
+macro_rules! impl_value {
+	($($t:ty),*) => {
+		$(
+			impl Value for $t {
+				const NAME: &'static str = stringify!($t);
+
+				fn from_u32(value: u32) -> Self {
+					value as $t
+				}
+
+				fn to_u32(&self) -> u32 {
+					*self as u32
+				}
+
+				fn to_char(&self) -> Option<char> {
+					u32::try_from(*self).ok().and_then(std::char::from_u32)
+				}
+
+				fn random<R: Rng + ?Sized>(rng: &mut R) -> Self {
+					rng.gen()
+				}
+
+				fn wrapping_add(&self, other: &Self) -> Self {
+					<$t>::wrapping_add(*self, *other)
+				}
+
+				fn wrapping_sub(&self, other: &Self) -> Self {
+					<$t>::wrapping_sub(*self, *other)
+				}
+
+				fn wrapping_mul(&self, other: &Self) -> Self {
+					<$t>::wrapping_mul(*self, *other)
+				}
+
+				fn wrapping_div(&self, other: &Self) -> Self {
+					if *other == 0 { 0 } else { <$t>::wrapping_div(*self, *other) }
+				}
+
+				fn wrapping_rem(&self, other: &Self) -> Self {
+					if *other == 0 { 0 } else { <$t>::wrapping_rem(*self, *other) }
+				}
+
+				fn checked_add(&self, other: &Self) -> Option<Self> {
+					<$t>::checked_add(*self, *other)
+				}
+
+				fn checked_sub(&self, other: &Self) -> Option<Self> {
+					<$t>::checked_sub(*self, *other)
+				}
+
+				fn checked_mul(&self, other: &Self) -> Option<Self> {
+					<$t>::checked_mul(*self, *other)
+				}
+
+				fn checked_div(&self, other: &Self) -> Option<Self> {
+					if *other == 0 { Some(0) } else { <$t>::checked_div(*self, *other) }
+				}
+
+				fn checked_rem(&self, other: &Self) -> Option<Self> {
+					if *other == 0 { Some(0) } else { <$t>::checked_rem(*self, *other) }
+				}
+
+				fn saturating_add(&self, other: &Self) -> Self {
+					<$t>::saturating_add(*self, *other)
+				}
+
+				fn saturating_sub(&self, other: &Self) -> Self {
+					<$t>::saturating_sub(*self, *other)
+				}
+
+				fn saturating_mul(&self, other: &Self) -> Self {
+					<$t>::saturating_mul(*self, *other)
+				}
+
+				fn saturating_div(&self, other: &Self) -> Self {
+					if *other == 0 { 0 } else { <$t>::saturating_div(*self, *other) }
+				}
+
+				fn nand(&self, other: &Self) -> Self {
+					!(*self & *other)
+				}
+			}
+		)*
+	};
+}
+
+impl_value!(u8, u16, u32, u64, i64);